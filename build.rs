@@ -3,6 +3,8 @@
 // }
 
 fn main() {
+    // The window and FSUIPC only exist on Windows; elsewhere it runs headless
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() != Ok("windows") { return }
     embed_resource::compile("res/res.rc", embed_resource::NONE);
     println!("cargo:rustc-link-lib=static=FSUIPCuser64");
     println!("cargo:rustc-link-search=native=lib/");
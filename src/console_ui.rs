@@ -0,0 +1,61 @@
+use std::{collections::HashMap, process, sync::{Arc, Mutex}, thread, time::Duration};
+
+use crate::{core::{App, Preferences}, ui::{Message, Ui}};
+
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+
+
+/// Prints what's going on to the terminal, for running without a window where there is no
+/// Win32 UI. Settings come from the settings file alone.
+#[derive(Clone, Default)]
+pub struct ConsoleUi {
+    /// The last status line printed for each connection. Failures are reported every time
+    /// they are retried, which a status bar shrugs off but would flood the terminal.
+    statuses: Arc<Mutex<HashMap<&'static str, String>>>,
+}
+
+impl Ui for ConsoleUi {
+    fn dispatch_message(&self, message: Message) {
+        let (status, line) = match message {
+            Message::MsfsConnected => (Some("msfs"), String::from("Connected to MSFS")),
+            Message::MsfsDisconnected => (Some("msfs"), String::from("Disconnected from MSFS")),
+            Message::EuroscopeConnected(callsign) => (Some("euroscope"), format!("EuroScope connected as {}", callsign)),
+            Message::EuroscopeDisconnected => (Some("euroscope"), String::from("EuroScope disconnected")),
+            Message::MetarsRetrieved => (Some("metars"), String::from("METARs retrieved")),
+            Message::MetarsDisconnected => (Some("metars"), String::from("Could not retrieve METARs")),
+            Message::MetarNotFound => (None, String::from("METAR not found")),
+            Message::MetarRetrieved(metar) => (None, metar),
+            Message::MetarsRetrievedMultiple(metars) => (None, metars.join("\n")),
+            Message::VatsimDataRetrieved => (Some("vatsim"), String::from("VATSIM data retrieved")),
+            Message::VatsimDataDisconnected => (Some("vatsim"), String::from("Could not retrieve VATSIM data")),
+            Message::AirportsLoaded(count) => (None, format!("Loaded {} airports", count)),
+            Message::AirportsNotLoaded(reason) => (None, format!("Could not load airports from {}", reason)),
+            Message::SourceConnected(name) => (Some("source"), format!("Receiving traffic from {}", name)),
+            Message::SourceDisconnected => (Some("source"), String::from("Traffic source lost")),
+            Message::SquawkSet(code) => (None, format!("Squawk {:04} set from EuroScope", code)),
+            Message::TextMessageReceived(from, message) => (None, format!("{}: {}", from, message)),
+            Message::ConfigReloaded => (None, String::from("Settings reloaded")),
+            Message::AircraftCorrelated(sim_id, callsign, confidence) => (None, format!("{} matched to {} by position ({}% confidence)", sim_id, callsign, confidence)),
+            Message::FatalError(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            },
+        };
+        if let Some(status) = status {
+            let mut statuses = self.statuses.lock().unwrap();
+            if statuses.get(status) == Some(&line) { return }
+            statuses.insert(status, line.clone());
+        }
+        println!("{}", line);
+    }
+}
+
+/// Runs until the process is killed, picking up changes to the settings file as it is saved.
+pub fn run() -> ! {
+    let mut app = App::new(Preferences::new(true, true, true, false), ConsoleUi::default());
+    loop {
+        thread::sleep(CONFIG_CHECK_INTERVAL);
+        app.reload_config_if_changed();
+    }
+}
@@ -0,0 +1,218 @@
+use std::{collections::HashMap, fs, io, path::{Path, PathBuf}, sync::{Arc, Mutex}, thread};
+
+use crate::ui::{Message, Ui};
+
+use super::geo::{self, NM_PER_DEGREE_LAT};
+
+/// Looked for in the working directory unless `[airports] file` says otherwise. It isn't shipped,
+/// as OurAirports updates theirs daily; download `airports.csv` from https://ourairports.com/data/.
+pub const DEFAULT_AIRPORTS_FILE: &str = "airports.csv";
+const CELL_SIZE_DEG: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct Airport {
+    pub icao: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// Field elevation in feet, where the file gives one.
+    pub elevation_ft: Option<f64>,
+}
+
+/// Airport and station positions, loaded from an OurAirports-style `airports.csv`.
+#[derive(Clone)]
+pub struct AirportDatabase {
+    index: Arc<Mutex<AirportIndex>>,
+}
+
+impl AirportDatabase {
+    pub fn new() -> AirportDatabase {
+        AirportDatabase {
+            index: Arc::new(Mutex::new(AirportIndex::default())),
+        }
+    }
+
+    /// Fills the database from `path` on a background thread, and tells the UI how that went.
+    /// Without it, nothing that needs to know where airports are works. If the file can't be
+    /// read, whatever was loaded before is kept.
+    pub fn load_in_background<U: Ui + 'static>(&self, path: impl Into<PathBuf>, ui_link: U) {
+        let path = path.into();
+        let index = Arc::clone(&self.index);
+        thread::Builder::new().name("TrafficViewerAirportLoaderThread".into()).spawn(move || {
+            let message = match read_airports(&path) {
+                Ok(airports) => {
+                    let count = airports.len();
+                    *index.lock().unwrap() = AirportIndex::new(airports);
+                    Message::AirportsLoaded(count)
+                },
+                Err(e) => Message::AirportsNotLoaded(format!("{}: {}", path.display(), e)),
+            };
+            ui_link.dispatch_message(message);
+        }).ok();
+    }
+
+    /// Whether any airports have been loaded yet.
+    pub fn is_loaded(&self) -> bool {
+        !self.index.lock().unwrap().airports.is_empty()
+    }
+
+    pub fn get(&self, icao: &str) -> Option<Airport> {
+        let index = self.index.lock().unwrap();
+        index.by_icao.get(icao).map(|i| index.airports[*i].clone())
+    }
+
+    /// Finds the nearest airport within `max_distance_nm` for which `predicate` holds.
+    /// Returns the airport and its distance in nautical miles.
+    pub fn nearest(&self, lat: f64, lon: f64, max_distance_nm: f64, predicate: impl Fn(&Airport) -> bool) -> Option<(Airport, f64)> {
+        let index = self.index.lock().unwrap();
+        let mut radius = NM_PER_DEGREE_LAT * CELL_SIZE_DEG;
+        loop {
+            let radius_to_search = radius.min(max_distance_nm);
+            let best = index.candidates(lat, lon, radius_to_search)
+                .map(|airport| (airport, geo::distance_nm(lat, lon, airport.lat, airport.lon)))
+                .filter(|(airport, distance)| *distance <= radius_to_search && predicate(airport))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b));
+            if let Some((airport, distance)) = best {
+                return Some((airport.clone(), distance));
+            }
+            if radius_to_search >= max_distance_nm { return None; }
+            radius *= 2.0;
+        }
+    }
+}
+
+
+#[cfg(test)]
+impl AirportDatabase {
+    pub fn with_airports(airports: Vec<Airport>) -> AirportDatabase {
+        AirportDatabase { index: Arc::new(Mutex::new(AirportIndex::new(airports))) }
+    }
+}
+
+#[derive(Default)]
+struct AirportIndex {
+    airports: Vec<Airport>,
+    by_icao: HashMap<String, usize>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl AirportIndex {
+    fn new(airports: Vec<Airport>) -> AirportIndex {
+        let mut by_icao = HashMap::with_capacity(airports.len());
+        let mut cells: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (i, airport) in airports.iter().enumerate() {
+            by_icao.insert(airport.icao.clone(), i);
+            cells.entry(cell_of(airport.lat, airport.lon)).or_default().push(i);
+        }
+        AirportIndex { airports, by_icao, cells }
+    }
+
+    /// Airports in every grid cell overlapping the box that bounds the circle of `radius_nm`.
+    fn candidates(&self, lat: f64, lon: f64, radius_nm: f64) -> impl Iterator<Item = &Airport> {
+        let lat_span = radius_nm / NM_PER_DEGREE_LAT;
+        let widest_lat = (lat.abs() + lat_span).min(89.9);
+        let lon_span = (radius_nm / (NM_PER_DEGREE_LAT * widest_lat.to_radians().cos())).min(180.0);
+
+        let (min_lat_cell, max_lat_cell) = (((lat - lat_span) / CELL_SIZE_DEG).floor() as i32, ((lat + lat_span) / CELL_SIZE_DEG).floor() as i32);
+        let (min_lon_cell, max_lon_cell) = (((lon - lon_span) / CELL_SIZE_DEG).floor() as i32, ((lon + lon_span) / CELL_SIZE_DEG).floor() as i32);
+        let lon_cells_in_world = (360.0 / CELL_SIZE_DEG) as i32;
+        let lon_cell_count = (max_lon_cell - min_lon_cell + 1).min(lon_cells_in_world);
+
+        (min_lat_cell..=max_lat_cell).flat_map(move |lat_cell| {
+            (0..lon_cell_count).map(move |i| (lat_cell, wrap_lon_cell(min_lon_cell + i)))
+        }).filter_map(|cell| self.cells.get(&cell)).flatten().map(|i| &self.airports[*i])
+    }
+}
+
+fn cell_of(lat: f64, lon: f64) -> (i32, i32) {
+    ((lat / CELL_SIZE_DEG).floor() as i32, wrap_lon_cell((lon / CELL_SIZE_DEG).floor() as i32))
+}
+
+fn wrap_lon_cell(cell: i32) -> i32 {
+    let lon_cells_in_world = (360.0 / CELL_SIZE_DEG) as i32;
+    let half = lon_cells_in_world / 2;
+    (cell + half).rem_euclid(lon_cells_in_world) - half
+}
+
+fn read_airports(path: &Path) -> io::Result<Vec<Airport>> {
+    let contents = fs::read_to_string(path)?;
+    let mut lines = contents.lines();
+    let header = split_csv_line(lines.next().unwrap_or_default());
+    let column = |name: &str| header.iter().position(|column| column.eq_ignore_ascii_case(name));
+
+    let missing_column = |name: &str| io::Error::new(io::ErrorKind::InvalidData, format!("no {} column", name));
+    let icao_column = column("ident").or_else(|| column("icao")).ok_or_else(|| missing_column("ident"))?;
+    let lat_column = column("latitude_deg").or_else(|| column("lat")).ok_or_else(|| missing_column("latitude_deg"))?;
+    let lon_column = column("longitude_deg").or_else(|| column("lon")).ok_or_else(|| missing_column("longitude_deg"))?;
+    let elevation_column = column("elevation_ft");
+    let type_column = column("type");
+
+    let airports = lines.filter_map(|line| {
+        let fields = split_csv_line(line);
+        if type_column.and_then(|i| fields.get(i)).is_some_and(|airport_type| airport_type == "closed") {
+            return None;
+        }
+        Some(Airport {
+            icao: fields.get(icao_column)?.to_uppercase(),
+            lat: fields.get(lat_column)?.parse().ok()?,
+            lon: fields.get(lon_column)?.parse().ok()?,
+            elevation_ft: elevation_column.and_then(|i| fields.get(i)).and_then(|elevation| elevation.parse().ok()),
+        })
+    }).collect();
+    Ok(airports)
+}
+
+/// Splits one CSV record, honouring double-quoted fields and `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            },
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unique to the test and the process, so parallel runs don't trip over each other.
+    fn write_temp(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("traffic-viewer-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_open_airports() {
+        let path = write_temp("open-airports.csv", "\"id\",\"ident\",\"type\",\"name\",\"latitude_deg\",\"longitude_deg\",\"elevation_ft\"\n\
+            1,\"egll\",\"large_airport\",\"London Heathrow, UK\",51.4706,-0.461941,83\n\
+            2,\"EGXX\",\"closed\",\"Gone\",52.0,-1.0,100\n\
+            3,\"XXHP\",\"heliport\",\"Rooftop\",51.5,-0.1,\n");
+        let airports = read_airports(&path).unwrap();
+        fs::remove_file(&path).ok();
+        assert_eq!(airports.len(), 2);
+        assert_eq!(airports[0].icao, "EGLL");
+        assert!((airports[0].lon + 0.461941).abs() < 1e-9);
+        assert_eq!(airports[0].elevation_ft, Some(83.0));
+        assert_eq!(airports[1].elevation_ft, None);
+    }
+
+    #[test]
+    fn reports_missing_columns_and_files() {
+        let path = write_temp("missing-columns.csv", "ident,name\nEGLL,Heathrow\n");
+        assert!(read_airports(&path).unwrap_err().to_string().contains("latitude_deg"));
+        fs::remove_file(&path).ok();
+        assert!(read_airports(Path::new("/nonexistent/airports.csv")).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Conversions between pressure altitude and altimeter readings.
+//!
+//! All altitudes are in feet unless a function says otherwise. The relationships are those
+//! of the ICAO standard atmosphere, so no temperature correction is applied.
+
+pub const STANDARD_PRESSURE_HPA: f64 = 1013.25;
+pub const HPA_PER_IN_HG: f64 = 33.8639;
+pub const FEET_PER_METRE: f64 = 3.28084;
+
+const ISA_HEIGHT_CONSTANT_FT: f64 = 145366.45;
+const ISA_EXPONENT: f64 = 0.190263;
+/// Altimeter settings within this much of standard are treated as standard.
+const STANDARD_SETTING_TOLERANCE_HPA: f64 = 0.5;
+
+
+
+pub fn in_hg_to_hpa(in_hg: f64) -> f64 {
+    in_hg * HPA_PER_IN_HG
+}
+
+pub fn metres_to_feet(metres: f64) -> f64 {
+    metres * FEET_PER_METRE
+}
+
+/// The static pressure in hPa at a given pressure altitude.
+pub fn pressure_at(pressure_altitude_ft: f64) -> f64 {
+    STANDARD_PRESSURE_HPA * (1.0 - pressure_altitude_ft / ISA_HEIGHT_CONSTANT_FT).powf(1.0 / ISA_EXPONENT)
+}
+
+/// The altitude an altimeter set to `setting_hpa` reads at a given pressure altitude.
+pub fn indicated_altitude(pressure_altitude_ft: f64, setting_hpa: f64) -> f64 {
+    let static_pressure = pressure_at(pressure_altitude_ft);
+    ISA_HEIGHT_CONSTANT_FT * (1.0 - (static_pressure / setting_hpa).powf(ISA_EXPONENT))
+}
+
+/// The pressure altitude at which an altimeter set to `setting_hpa` reads `indicated_altitude_ft`.
+pub fn pressure_altitude(indicated_altitude_ft: f64, setting_hpa: f64) -> f64 {
+    let static_pressure = setting_hpa * (1.0 - indicated_altitude_ft / ISA_HEIGHT_CONSTANT_FT).powf(1.0 / ISA_EXPONENT);
+    ISA_HEIGHT_CONSTANT_FT * (1.0 - (static_pressure / STANDARD_PRESSURE_HPA).powf(ISA_EXPONENT))
+}
+
+pub fn is_standard_setting(setting_hpa: f64) -> bool {
+    (setting_hpa - STANDARD_PRESSURE_HPA).abs() <= STANDARD_SETTING_TOLERANCE_HPA
+}
+
+
+/// The transition altitude of an area and the transition level derived from it.
+#[derive(Debug, Clone, Copy)]
+pub struct Transition {
+    pub altitude_ft: f64,
+}
+
+impl Transition {
+    pub fn new(altitude_ft: f64) -> Transition {
+        Transition { altitude_ft }
+    }
+
+    /// The lowest flight level, in 500 ft steps, that is at least 1000 ft above the transition
+    /// altitude on the given QNH.
+    pub fn level(&self, qnh_hpa: f64) -> u32 {
+        let mut flight_level = (self.altitude_ft / 100.0).ceil() as u32;
+        flight_level += (5 - flight_level % 5) % 5;
+        while indicated_altitude(flight_level as f64 * 100.0, qnh_hpa) < self.altitude_ft + 1000.0 {
+            flight_level += 5;
+        }
+        flight_level
+    }
+
+    /// Whether an aircraft should have its altimeter set to standard rather than QNH.
+    pub fn is_above(&self, pressure_altitude_ft: f64, qnh_hpa: f64) -> bool {
+        indicated_altitude(pressure_altitude_ft, qnh_hpa) > self.altitude_ft
+    }
+}
+
+impl Default for Transition {
+    fn default() -> Transition {
+        Transition::new(6000.0)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pressure_and_indicated_altitude_round_trip() {
+        for setting in [983.0, 1013.25, 1041.0, in_hg_to_hpa(29.42)] {
+            for pressure_alt in [-500.0, 0.0, 3000.0, 12500.0, 41000.0] {
+                let indicated = indicated_altitude(pressure_alt, setting);
+                assert!((pressure_altitude(indicated, setting) - pressure_alt).abs() < 0.01, "{} ft on {} hPa", pressure_alt, setting);
+            }
+        }
+    }
+
+    #[test]
+    fn low_qnh_reads_lower_than_pressure_altitude() {
+        // Roughly 27 ft per hPa near the surface.
+        let indicated = indicated_altitude(1000.0, 1003.25);
+        assert!((indicated - 720.0).abs() < 10.0, "{}", indicated);
+        assert!((pressure_altitude(0.0, 1023.25) + 275.0).abs() < 10.0);
+        assert!(is_standard_setting(in_hg_to_hpa(29.92)));
+    }
+
+    #[test]
+    fn transition_level_keeps_a_layer_above_the_transition_altitude() {
+        let transition = Transition::new(6000.0);
+        assert_eq!(transition.level(1013.25), 70);
+        assert_eq!(transition.level(1030.0), 70);
+        // Below about 1010 hPa FL70 is less than 1000 ft above 6000 ft on QNH.
+        assert_eq!(transition.level(1005.0), 75);
+        assert_eq!(transition.level(970.0), 85);
+        assert_eq!(Transition::new(18000.0).level(1013.25), 190);
+    }
+
+    #[test]
+    fn above_transition_altitude_on_qnh() {
+        let transition = Transition::default();
+        assert!(transition.is_above(6100.0, STANDARD_PRESSURE_HPA));
+        assert!(!transition.is_above(5900.0, STANDARD_PRESSURE_HPA));
+        // FL62 on a high QNH is well above 6000 ft.
+        assert!(transition.is_above(5800.0, 1030.0));
+    }
+}
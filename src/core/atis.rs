@@ -0,0 +1,160 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use super::{altimetry::Transition, metar::{DecodedMetar, MetarProvider}, vatsim::VatsimDataProvider, Preferences};
+
+
+
+#[derive(Clone)]
+pub struct AtisProvider {
+    metar_provider: MetarProvider,
+    vatsim_data_provider: VatsimDataProvider,
+    preferences: Preferences,
+    generated: Arc<Mutex<HashMap<String, GeneratedAtis>>>,
+}
+
+impl AtisProvider {
+    pub fn new(metar_provider: MetarProvider, vatsim_data_provider: VatsimDataProvider, preferences: Preferences) -> AtisProvider {
+        AtisProvider {
+            metar_provider,
+            vatsim_data_provider,
+            preferences,
+            generated: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Returns the ATIS text lines for a station callsign such as `EGLL_ATIS`.
+    ///
+    /// The VATSIM ATIS is used if the station is online; otherwise one is generated from the
+    /// cached METAR for the airport.
+    pub fn get_atis(&self, station_callsign: &str) -> Option<Vec<String>> {
+        if let Some(lines) = self.vatsim_data_provider.get_atis(station_callsign).and_then(|atis| atis.text_atis).filter(|lines| !lines.is_empty()) {
+            return Some(lines);
+        }
+
+        let icao = station_callsign.split('_').next().unwrap_or(station_callsign);
+        let raw_metar = self.metar_provider.lookup_metar(icao)?;
+        let metar = DecodedMetar::parse(&raw_metar)?;
+
+        let mut generated = self.generated.lock().unwrap();
+        let atis = generated.entry(icao.to_owned()).or_insert_with(|| GeneratedAtis { metar: raw_metar.clone(), letter: 'A' });
+        if atis.metar != raw_metar {
+            atis.metar = raw_metar;
+            atis.letter = next_letter(atis.letter);
+        }
+        let runways = self.preferences.atis_runways(icao);
+        Some(generate_text(&metar, atis.letter, select_runway(&runways, &metar), self.preferences.transition()))
+    }
+}
+
+
+struct GeneratedAtis {
+    metar: String,
+    letter: char,
+}
+
+fn next_letter(letter: char) -> char {
+    if letter >= 'Z' { 'A' } else { (letter as u8 + 1) as char }
+}
+
+/// Picks the runway with the greatest headwind component, or the first runway listed if
+/// the wind is calm or variable.
+fn select_runway<'a>(runways: &'a [String], metar: &DecodedMetar) -> Option<&'a str> {
+    let wind_direction = metar.wind.filter(|wind| wind.speed_kt > 0).and_then(|wind| wind.direction);
+    let wind_direction = match wind_direction {
+        Some(direction) => direction as f64,
+        None => return runways.first().map(|rwy| rwy.as_str()),
+    };
+
+    runways.iter().filter_map(|runway| {
+        let number = runway.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<f64>().ok()?;
+        let headwind = (wind_direction - number * 10.0).to_radians().cos();
+        Some((runway, headwind))
+    }).max_by(|(_, a), (_, b)| a.total_cmp(b)).map(|(runway, _)| runway.as_str())
+}
+
+fn generate_text(metar: &DecodedMetar, letter: char, runway: Option<&str>, transition: Transition) -> Vec<String> {
+    let mut lines = Vec::new();
+    let time = metar.time.map(|(_, hour, min)| format!(" TIME {:02}{:02}Z", hour, min)).unwrap_or_default();
+    lines.push(format!("{} INFORMATION {}{}", metar.station, letter, time));
+    if let Some(runway) = runway {
+        lines.push(format!("RUNWAY IN USE {}", runway));
+    }
+    if let Some(wind) = metar.wind {
+        let direction = wind.direction.map(|dir| format!("{:03}", dir)).unwrap_or_else(|| String::from("VRB"));
+        let gust = wind.gust_kt.map(|gust| format!(" GUSTING {}", gust)).unwrap_or_default();
+        lines.push(format!("WIND {} {} KT{}", direction, wind.speed_kt, gust));
+    }
+    if !metar.weather.is_empty() {
+        lines.push(metar.weather.join(" "));
+    }
+    if let Some(temperature) = metar.temperature {
+        let dewpoint = metar.dewpoint.map(|dew| format!(" DEWPOINT {}", dew)).unwrap_or_default();
+        lines.push(format!("TEMPERATURE {}{}", temperature, dewpoint));
+    }
+    if let Some(qnh) = metar.qnh_hpa {
+        lines.push(format!("QNH {:04}", qnh.round() as u32));
+        lines.push(format!("TRANSITION LEVEL {}", transition.level(qnh)));
+    }
+    lines.push(format!("ACKNOWLEDGE RECEIPT OF INFORMATION {} AND ADVISE AIRCRAFT TYPE ON FIRST CONTACT", letter));
+    lines
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::Config;
+
+    fn provider(config: &str) -> (AtisProvider, MetarProvider) {
+        let metar_provider = MetarProvider::new();
+        let mut preferences = Preferences::new(false, false, false, false);
+        preferences.load_config(&Config::parse(config));
+        (AtisProvider::new(metar_provider.clone(), VatsimDataProvider::new(), preferences), metar_provider)
+    }
+
+    #[test]
+    fn advances_the_letter_when_the_metar_changes() {
+        let (atis, metars) = provider("");
+        metars.insert_metar("EGLL", "EGLL 191150Z 24015KT CAVOK 14/11 Q1008");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[0], "EGLL INFORMATION A TIME 1150Z");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[0], "EGLL INFORMATION A TIME 1150Z");
+
+        metars.insert_metar("EGLL", "EGLL 191220Z 25016KT CAVOK 14/11 Q1008");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[0], "EGLL INFORMATION B TIME 1220Z");
+    }
+
+    #[test]
+    fn selects_the_runway_most_into_wind() {
+        let (atis, metars) = provider("[runways]\negll = 09l 27r\n");
+        metars.insert_metar("EGLL", "EGLL 191150Z 24015KT CAVOK 14/11 Q1008");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[1], "RUNWAY IN USE 27R");
+
+        metars.insert_metar("EGLL", "EGLL 191220Z 07010KT CAVOK 14/11 Q1008");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[1], "RUNWAY IN USE 09L");
+
+        metars.insert_metar("EGLL", "EGLL 191250Z VRB02KT CAVOK 14/11 Q1008");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap()[1], "RUNWAY IN USE 09L");
+    }
+
+    #[test]
+    fn generates_the_text_from_the_metar() {
+        let (atis, metars) = provider("[runways]\nEGLL = 27R\n");
+        metars.insert_metar("EGLL", "EGLL 191150Z 24015G25KT 9999 -RA BKN012 14/11 Q1008 NOSIG");
+        assert_eq!(atis.get_atis("EGLL_ATIS").unwrap(), [
+            "EGLL INFORMATION A TIME 1150Z",
+            "RUNWAY IN USE 27R",
+            "WIND 240 15 KT GUSTING 25",
+            "9999 -RA BKN012",
+            "TEMPERATURE 14 DEWPOINT 11",
+            "QNH 1008",
+            "TRANSITION LEVEL 75",
+            "ACKNOWLEDGE RECEIPT OF INFORMATION A AND ADVISE AIRCRAFT TYPE ON FIRST CONTACT",
+        ]);
+    }
+
+    #[test]
+    fn has_nothing_without_a_metar() {
+        let (atis, _) = provider("");
+        assert!(atis.get_atis("EGLL_ATIS").is_none());
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use super::{config::Config, enrichment::AircraftInfo};
+
+
+
+/// Works out which network callsign a sim aircraft is flying under.
+///
+/// The sim's ATC id is often a tail number or a model-matched name rather than the callsign the
+/// pilot connected with, so besides the id itself we try:
+///  - an explicit entry from the `[callsign_map]` section, `sim id = network callsign`
+///  - the id with dashes and spaces taken out, so `G-ABCD` becomes `GABCD`
+///  - an airline ICAO code and flight number without leading zeros, so `BAW 0123` becomes `BAW123`
+///  - the same with an IATA code or telephony designator from `[airlines]` turned into the ICAO
+///    code, such as `BA = BAW` or `SPEEDBIRD = BAW`, using the sim's airline and flight number too
+#[derive(Debug, Clone, Default)]
+pub struct CallsignMap {
+    explicit: HashMap<String, String>,
+    airlines: HashMap<String, String>,
+}
+
+impl CallsignMap {
+    pub fn from_config(config: &Config) -> CallsignMap {
+        let read_section = |name: &str| config.section(name).iter().map(|(key, value)| (normalise(key), normalise(value))).collect();
+        CallsignMap {
+            explicit: read_section("callsign_map"),
+            airlines: read_section("airlines"),
+        }
+    }
+
+    /// Callsigns the aircraft might be using, most likely first and without repeats.
+    pub fn candidates(&self, sim_id: &str, info: Option<&AircraftInfo>) -> Vec<String> {
+        let mut candidates = Vec::new();
+        let mut add = |candidate: String| {
+            if !candidate.is_empty() && !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        };
+
+        let stripped = normalise(sim_id);
+        if let Some(mapped) = self.explicit.get(&stripped) {
+            add(mapped.clone());
+        }
+        add(sim_id.trim().to_owned());
+        add(stripped.clone());
+        if let Some(flight) = self.airline_flight(&stripped) {
+            add(flight);
+        }
+        if let Some(flight) = info.and_then(|info| self.airline_flight(&normalise(&info.airline_and_flight_number))) {
+            add(flight);
+        }
+        candidates
+    }
+
+    /// The first candidate for which `is_known` holds, or failing that the explicit mapping,
+    /// or the sim id as it is.
+    pub fn resolve(&self, sim_id: &str, info: Option<&AircraftInfo>, is_known: impl Fn(&str) -> bool) -> String {
+        let candidates = self.candidates(sim_id, info);
+        candidates.iter().find(|candidate| is_known(candidate)).cloned()
+            .or_else(|| candidates.into_iter().next())
+            .unwrap_or_else(|| sim_id.to_owned())
+    }
+
+    /// Turns an airline designator and flight number into the ICAO form, if it is one.
+    fn airline_flight(&self, stripped: &str) -> Option<String> {
+        let split = stripped.find(|c: char| c.is_ascii_digit()).filter(|split| *split > 0)?;
+        let (airline, number) = stripped.split_at(split);
+        if !number.chars().all(|c| c.is_ascii_alphanumeric()) { return None; }
+        let airline = self.airlines.get(airline).map(|icao| icao.as_str()).unwrap_or(airline);
+        if airline.len() != 3 || !airline.chars().all(|c| c.is_ascii_alphabetic()) { return None; }
+        let number = number.trim_start_matches('0');
+        if number.is_empty() { return None; }
+        Some(format!("{}{}", airline, number))
+    }
+}
+
+/// Uppercase with dashes, spaces and other punctuation taken out.
+fn normalise(callsign: &str) -> String {
+    callsign.chars().filter(|c| c.is_ascii_alphanumeric()).map(|c| c.to_ascii_uppercase()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn callsign_map() -> CallsignMap {
+        CallsignMap::from_config(&Config::parse("[callsign_map]\nG-ABCD = EXS12AB\n\
+            [airlines]\nBA = BAW\nSPEEDBIRD = BAW\n"))
+    }
+
+    #[test]
+    fn maps_exact_sim_ids() {
+        let callsign_map = callsign_map();
+        assert_eq!(callsign_map.candidates("G-ABCD", None), ["EXS12AB", "G-ABCD", "GABCD"]);
+        assert_eq!(callsign_map.resolve("G-ABCD", None, |_| false), "EXS12AB");
+        // Found on the network as it is, the sim id wins over the mapping
+        assert_eq!(callsign_map.resolve("G-ABCD", None, |callsign| callsign == "GABCD"), "GABCD");
+        assert_eq!(callsign_map.resolve("G-ABCE", None, |_| false), "G-ABCE");
+    }
+
+    #[test]
+    fn turns_airline_prefixes_into_icao_codes() {
+        let callsign_map = callsign_map();
+        assert_eq!(callsign_map.resolve("BA0123", None, |callsign| callsign == "BAW123"), "BAW123");
+        assert_eq!(callsign_map.resolve("SPEEDBIRD 12", None, |callsign| callsign == "BAW12"), "BAW12");
+        assert_eq!(callsign_map.resolve("EIN 0045", None, |callsign| callsign == "EIN45"), "EIN45");
+        // Neither a known airline nor an ICAO code
+        assert_eq!(callsign_map.candidates("XY123", None), ["XY123"]);
+
+        let info = AircraftInfo { airline_and_flight_number: String::from("Speedbird 0456"), ..AircraftInfo::default() };
+        assert_eq!(callsign_map.resolve("G-EUPA", Some(&info), |callsign| callsign == "BAW456"), "BAW456");
+    }
+
+    #[test]
+    fn ignores_case() {
+        let callsign_map = CallsignMap::from_config(&Config::parse("[callsign_map]\ng-abcd = exs12ab\n[airlines]\nba = baw\n"));
+        assert_eq!(callsign_map.resolve("G-ABCD", None, |_| false), "EXS12AB");
+        assert_eq!(callsign_map.resolve("g-abcd", None, |_| false), "EXS12AB");
+        assert_eq!(callsign_map.resolve("ba123", None, |callsign| callsign == "BAW123"), "BAW123");
+    }
+}
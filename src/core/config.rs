@@ -0,0 +1,62 @@
+use std::{collections::HashMap, fs, path::Path};
+
+
+
+/// A simple INI-style settings file.
+///
+/// Lines are `key = value` pairs grouped under `[section]` headers. Blank lines and lines
+/// starting with `;` or `#` are ignored. Section names are case-insensitive; keys keep their case.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    sections: HashMap<String, Vec<(String, String)>>,
+}
+
+impl Config {
+    /// Loads the file at `path`. A missing or unreadable file gives an empty config.
+    pub fn load(path: impl AsRef<Path>) -> Config {
+        match fs::read_to_string(path) {
+            Ok(contents) => Config::parse(&contents),
+            Err(_) => Config::default(),
+        }
+    }
+
+    pub fn parse(contents: &str) -> Config {
+        let mut sections: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        let mut current_section = String::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') { continue; }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                current_section = line[1..line.len() - 1].trim().to_lowercase();
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                sections.entry(current_section.clone()).or_default().push((key.trim().to_owned(), value.trim().to_owned()));
+            }
+        }
+        Config { sections }
+    }
+
+    /// All `key = value` pairs in a section, in file order.
+    pub fn section(&self, name: &str) -> &[(String, String)] {
+        self.sections.get(&name.to_lowercase()).map(|entries| entries.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.section(section).iter().rev().find(|(k, _)| k.eq_ignore_ascii_case(key)).map(|(_, v)| v.as_str())
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Option<bool> {
+        match self.get(section, key)?.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Some(true),
+            "0" | "false" | "no" | "off" => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn get_parsed<T: std::str::FromStr>(&self, section: &str, key: &str) -> Option<T> {
+        self.get(section, key)?.parse().ok()
+    }
+}
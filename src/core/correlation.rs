@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+
+use super::{config::Config, geo, vatsim::VatsimDataProvider};
+
+/// Matches scoring below this are too doubtful to attach someone's flight plan to.
+const MIN_CONFIDENCE: f64 = 0.3;
+/// An existing match is kept until the aircraft drift this many times the tolerances apart.
+const KEEP_FACTOR: f64 = 2.0;
+
+
+
+/// Tolerances from the `[correlation]` section.
+#[derive(Debug, Clone)]
+pub struct CorrelationSettings {
+    pub enabled: bool,
+    pub max_distance_nm: f64,
+    pub max_altitude_difference_ft: f64,
+    pub max_speed_difference_kt: f64,
+}
+
+impl CorrelationSettings {
+    pub fn from_config(config: &Config) -> CorrelationSettings {
+        let default = CorrelationSettings::default();
+        CorrelationSettings {
+            enabled: config.get_bool("correlation", "enabled").unwrap_or(default.enabled),
+            max_distance_nm: config.get_parsed("correlation", "max_distance").unwrap_or(default.max_distance_nm),
+            max_altitude_difference_ft: config.get_parsed("correlation", "max_altitude_difference").unwrap_or(default.max_altitude_difference_ft),
+            max_speed_difference_kt: config.get_parsed("correlation", "max_speed_difference").unwrap_or(default.max_speed_difference_kt),
+        }
+    }
+}
+
+impl Default for CorrelationSettings {
+    fn default() -> CorrelationSettings {
+        // The VATSIM feed is only refreshed every 15 seconds, so positions can be a few miles stale
+        CorrelationSettings { enabled: true, max_distance_nm: 3.0, max_altitude_difference_ft: 1000.0, max_speed_difference_kt: 60.0 }
+    }
+}
+
+/// Where a sim aircraft is, for comparing against VATSIM pilots.
+#[derive(Debug, Clone, Copy)]
+pub struct Observation {
+    pub lat: f64,
+    pub lon: f64,
+    pub alt: f64,
+    pub gs: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Correlation {
+    pub callsign: String,
+    /// From 0 to 1.
+    pub confidence: f64,
+}
+
+/// Pairs sim aircraft whose callsign didn't match anyone on VATSIM with the pilot flying
+/// closest to them, and sticks with that pairing while it stays plausible.
+#[derive(Default)]
+pub struct Correlator {
+    correlations: HashMap<u32, Correlation>,
+}
+
+impl Correlator {
+    pub fn new() -> Correlator {
+        Correlator::default()
+    }
+
+    /// The VATSIM pilot sim aircraft `id` is taken to be, and whether the match is new.
+    /// `is_taken` says whether a callsign already belongs to another aircraft.
+    pub fn correlate(&mut self, id: u32, observation: Observation, settings: &CorrelationSettings, vatsim_data_provider: &VatsimDataProvider, is_taken: impl Fn(&str) -> bool) -> Option<(Correlation, bool)> {
+        if !settings.enabled {
+            self.correlations.remove(&id);
+            return None;
+        }
+
+        if let Some(existing) = self.correlations.get(&id) {
+            // A callsign match elsewhere beats a position match
+            let still_plausible = !is_taken(&existing.callsign) && vatsim_data_provider.get_aircraft_details(&existing.callsign)
+                .and_then(|details| score(observation, &details, settings, KEEP_FACTOR))
+                .is_some();
+            if still_plausible {
+                return Some((existing.clone(), false));
+            }
+            self.correlations.remove(&id);
+        }
+
+        let mut scored: Vec<(String, f64)> = vatsim_data_provider.aircraft_near(observation.lat, observation.lon, settings.max_distance_nm).into_iter()
+            .filter(|details| !is_taken(&details.callsign) && !self.correlations.values().any(|correlation| correlation.callsign == details.callsign))
+            .filter_map(|details| score(observation, &details, settings, 1.0).map(|score| (details.callsign, score)))
+            .collect();
+        scored.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let (callsign, best) = scored.first().cloned()?;
+        // Someone else nearly as close makes it a coin toss
+        let runner_up = scored.get(1).map(|(_, score)| *score).unwrap_or(0.0);
+        let confidence = best - runner_up * 0.5;
+        if confidence < MIN_CONFIDENCE { return None; }
+
+        let correlation = Correlation { callsign, confidence };
+        self.correlations.insert(id, correlation.clone());
+        Some((correlation, true))
+    }
+
+    pub fn release(&mut self, id: u32) {
+        self.correlations.remove(&id);
+    }
+}
+
+/// How alike an observation and a VATSIM position are, from 0 to 1, or `None` if they are
+/// further apart than the tolerances scaled by `tolerance_factor`.
+fn score(observation: Observation, details: &super::vatsim::Details, settings: &CorrelationSettings, tolerance_factor: f64) -> Option<f64> {
+    let distance = geo::distance_nm(observation.lat, observation.lon, details.latitude, details.longitude) / (settings.max_distance_nm * tolerance_factor);
+    let altitude = (observation.alt - details.altitude as f64).abs() / (settings.max_altitude_difference_ft * tolerance_factor);
+    let speed = (observation.gs - details.groundspeed as f64).abs() / (settings.max_speed_difference_kt * tolerance_factor);
+    if distance > 1.0 || altitude > 1.0 || speed > 1.0 { return None; }
+    Some(1.0 - (distance + altitude + speed) / 3.0)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::vatsim::Details;
+
+    /// A degree of latitude is 60 nm.
+    const NM: f64 = 1.0 / 60.0;
+    const SIM: Observation = Observation { lat: 53.0, lon: -6.0, alt: 10000.0, gs: 250.0 };
+
+    fn pilots(pilots: &[Details]) -> VatsimDataProvider {
+        let vatsim_data_provider = VatsimDataProvider::new();
+        for pilot in pilots {
+            vatsim_data_provider.insert_aircraft(pilot.clone());
+        }
+        vatsim_data_provider
+    }
+
+    fn correlate(correlator: &mut Correlator, vatsim_data_provider: &VatsimDataProvider) -> Option<(Correlation, bool)> {
+        correlator.correlate(1, SIM, &CorrelationSettings::default(), vatsim_data_provider, |_| false)
+    }
+
+    #[test]
+    fn matches_the_pilot_flying_alongside() {
+        let vatsim_data_provider = pilots(&[Details::at("EIN123", 53.0 + NM, -6.0, 10200, 260)]);
+        let mut correlator = Correlator::new();
+        let (correlation, new) = correlate(&mut correlator, &vatsim_data_provider).unwrap();
+        assert_eq!(correlation.callsign, "EIN123");
+        assert!(new);
+        let (correlation, new) = correlate(&mut correlator, &vatsim_data_provider).unwrap();
+        assert_eq!(correlation.callsign, "EIN123");
+        assert!(!new);
+    }
+
+    #[test]
+    fn leaves_pilots_too_far_away() {
+        let vatsim_data_provider = pilots(&[Details::at("EIN123", 53.0 + 4.0 * NM, -6.0, 10000, 250)]);
+        assert!(correlate(&mut Correlator::new(), &vatsim_data_provider).is_none());
+    }
+
+    #[test]
+    fn leaves_pilots_at_another_level() {
+        let vatsim_data_provider = pilots(&[Details::at("EIN123", 53.0, -6.0, 11500, 250)]);
+        assert!(correlate(&mut Correlator::new(), &vatsim_data_provider).is_none());
+    }
+
+    #[test]
+    fn keeps_a_match_until_the_pilot_drifts_well_out_of_tolerance() {
+        let vatsim_data_provider = pilots(&[Details::at("EIN123", 53.0, -6.0, 10000, 250)]);
+        let mut correlator = Correlator::new();
+        assert!(correlate(&mut correlator, &vatsim_data_provider).is_some());
+
+        vatsim_data_provider.insert_aircraft(Details::at("EIN123", 53.0 + 5.0 * NM, -6.0, 10000, 250));
+        assert!(!correlate(&mut correlator, &vatsim_data_provider).unwrap().1);
+        vatsim_data_provider.insert_aircraft(Details::at("EIN123", 53.0 + 7.0 * NM, -6.0, 10000, 250));
+        assert!(correlate(&mut correlator, &vatsim_data_provider).is_none());
+    }
+
+    #[test]
+    fn prefers_the_closest_pilot() {
+        let vatsim_data_provider = pilots(&[
+            Details::at("EIN123", 53.0 + 2.0 * NM, -6.0, 10400, 270),
+            Details::at("RYR45", 53.0, -6.0 + 0.2 * NM, 10000, 250),
+        ]);
+        assert_eq!(correlate(&mut Correlator::new(), &vatsim_data_provider).unwrap().0.callsign, "RYR45");
+    }
+
+    #[test]
+    fn leaves_a_coin_toss_between_two_pilots() {
+        // Each halfway out on every tolerance, which on its own would do
+        let halfway = |callsign: &str, lat: f64| Details::at(callsign, lat, -6.0, 10500, 280);
+        let vatsim_data_provider = pilots(&[halfway("EIN123", 53.0 + 1.5 * NM)]);
+        assert!(correlate(&mut Correlator::new(), &vatsim_data_provider).is_some());
+
+        let vatsim_data_provider = pilots(&[halfway("EIN123", 53.0 + 1.5 * NM), halfway("RYR45", 53.0 - 1.5 * NM)]);
+        assert!(correlate(&mut Correlator::new(), &vatsim_data_provider).is_none());
+    }
+
+    #[test]
+    fn skips_callsigns_already_taken() {
+        let vatsim_data_provider = pilots(&[Details::at("EIN123", 53.0, -6.0, 10000, 250)]);
+        assert!(Correlator::new().correlate(1, SIM, &CorrelationSettings::default(), &vatsim_data_provider, |callsign| callsign == "EIN123").is_none());
+
+        // Nor is one pilot matched to two sim aircraft
+        let mut correlator = Correlator::new();
+        assert!(correlate(&mut correlator, &vatsim_data_provider).is_some());
+        assert!(correlator.correlate(2, SIM, &CorrelationSettings::default(), &vatsim_data_provider, |_| false).is_none());
+    }
+}
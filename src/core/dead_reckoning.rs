@@ -0,0 +1,125 @@
+use std::time::{Duration, Instant};
+
+use super::{geo, track::{Track, TrackSample}};
+
+/// Tracks are not extrapolated further than this past their last sample.
+pub const MAX_EXTRAPOLATION: Duration = Duration::from_secs(12);
+/// Length of each integration step, so that turns are followed rather than cut across.
+const STEP_SECS: f64 = 0.5;
+/// Extrapolated turns are limited to a standard rate turn.
+const MAX_HEADING_RATE: f64 = 3.0;
+
+
+
+/// Predicts where a track is at `at`, assuming it holds its last ground speed, vertical speed
+/// and rate of turn. Returns `None` if the last sample is too old to extrapolate from.
+pub fn extrapolate(track: &Track, at: Instant) -> Option<TrackSample> {
+    let sample = track.latest;
+    let elapsed = at.checked_duration_since(sample.time)?;
+    if elapsed > MAX_EXTRAPOLATION { return None; }
+    if sample.on_ground && sample.gs < 1.0 {
+        return Some(TrackSample { time: at, ..sample });
+    }
+
+    let heading_rate = track.heading_rate().clamp(-MAX_HEADING_RATE, MAX_HEADING_RATE);
+    let (mut lat, mut lon, mut hdg) = (sample.lat, sample.lon, sample.hdg);
+    let mut remaining = elapsed.as_secs_f64();
+    while remaining > 0.0 {
+        let step = remaining.min(STEP_SECS);
+        // Move along the heading at the middle of the step
+        let mid_hdg = hdg + heading_rate * step / 2.0;
+        (lat, lon) = geo::destination(lat, lon, mid_hdg, sample.gs * step / 3600.0);
+        hdg += heading_rate * step;
+        remaining -= step;
+    }
+
+    let climb = sample.vs * elapsed.as_secs_f64() / 60.0;
+    let true_alt = if sample.on_ground { sample.true_alt } else { sample.true_alt + climb };
+    let pressure_alt = sample.pressure_alt + (true_alt - sample.true_alt);
+    Some(TrackSample {
+        lat,
+        lon,
+        true_alt,
+        pressure_alt,
+        hdg: hdg.rem_euclid(360.0),
+        time: at,
+        ..sample
+    })
+}
+
+/// A bound on the horizontal error of an extrapolation, in nautical miles, for an aircraft
+/// that may change its turn rate by up to a standard rate turn and its speed by 10%.
+pub fn error_bound_nm(track: &Track, at: Instant) -> f64 {
+    let elapsed = match at.checked_duration_since(track.latest.time) {
+        Some(elapsed) => elapsed.as_secs_f64(),
+        None => return 0.0,
+    };
+    let distance = track.latest.gs * elapsed / 3600.0;
+    let worst_heading_change = (MAX_HEADING_RATE * elapsed).min(180.0).to_radians();
+    distance * 0.1 + distance * (worst_heading_change / 2.0).sin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A departure flown once a second: a straight climb out, a standard rate turn onto a new
+    /// heading while accelerating, and a straight leg again. Each entry is the heading rate and
+    /// acceleration in knots per second held for that many seconds.
+    const PROFILE: [(u32, f64, f64); 3] = [(30, 0.0, 0.0), (40, 3.0, 0.5), (30, 0.0, 0.0)];
+
+    fn record_track() -> Vec<TrackSample> {
+        let start = Instant::now();
+        let mut sample = TrackSample {
+            lat: 51.4700, lon: -0.4543, true_alt: 3000.0, pressure_alt: 3000.0, gs: 220.0,
+            hdg: 270.0, vs: 1500.0, pitch: 5.0, bank: 0.0, on_ground: false, time: start,
+        };
+        let mut samples = vec![sample];
+        for (secs, heading_rate, acceleration) in PROFILE {
+            for _ in 0..secs {
+                (sample.lat, sample.lon) = geo::destination(sample.lat, sample.lon, sample.hdg + heading_rate / 2.0, (sample.gs + acceleration / 2.0) / 3600.0);
+                sample.hdg = (sample.hdg + heading_rate).rem_euclid(360.0);
+                sample.gs += acceleration;
+                sample.true_alt += sample.vs / 60.0;
+                sample.time += Duration::from_secs(1);
+                samples.push(sample);
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn real_error_stays_within_the_bound() {
+        let samples = record_track();
+        let mut worst_ratio: f64 = 0.0;
+        for i in 1..samples.len() {
+            let track = Track { previous: Some(samples[i - 1]), ..Track::new(String::from("TEST"), samples[i]) };
+            for actual in samples.iter().skip(i + 1).take(MAX_EXTRAPOLATION.as_secs() as usize) {
+                let predicted = extrapolate(&track, actual.time).unwrap();
+                let error = geo::distance_nm(predicted.lat, predicted.lon, actual.lat, actual.lon);
+                let bound = error_bound_nm(&track, actual.time);
+                assert!(error <= bound + 1e-3, "after sample {}: {:.3} nm off, bound {:.3} nm", i, error, bound);
+                if bound > 0.0 { worst_ratio = worst_ratio.max(error / bound); }
+                assert!((predicted.true_alt - actual.true_alt).abs() < 1.0);
+            }
+        }
+        // The bound should be close enough to be worth having, not just large.
+        assert!(worst_ratio > 0.5, "{}", worst_ratio);
+    }
+
+    #[test]
+    fn steady_track_extrapolates_exactly() {
+        let samples = record_track();
+        let track = Track { previous: Some(samples[4]), ..Track::new(String::from("TEST"), samples[5]) };
+        let predicted = extrapolate(&track, samples[15].time).unwrap();
+        assert!(geo::distance_nm(predicted.lat, predicted.lon, samples[15].lat, samples[15].lon) < 0.01);
+    }
+
+    #[test]
+    fn does_not_extrapolate_stale_tracks() {
+        let samples = record_track();
+        let track = Track::new(String::from("TEST"), samples[0]);
+        assert!(extrapolate(&track, samples[0].time + MAX_EXTRAPOLATION + Duration::from_secs(1)).is_none());
+        assert_eq!(error_bound_nm(&track, samples[0].time), 0.0);
+    }
+}
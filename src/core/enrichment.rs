@@ -0,0 +1,175 @@
+use std::{collections::{hash_map, HashMap, VecDeque}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+
+use super::fsuipc::{self, StringType};
+
+/// How long to wait for FSUIPC to answer a string request before giving up on that string.
+const STRING_TIMEOUT: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// The strings fetched for each aircraft, in the order they are asked for.
+const STRINGS: [StringType; 4] = [StringType::AtcAcTypeAndModel, StringType::AirlineNameAndFlightNumber, StringType::TailNumber, StringType::AircraftTitle];
+
+
+
+/// What the sim knows about an AI aircraft beyond its TCAS entry. Strings the sim did not
+/// provide are left empty.
+#[derive(Debug, Clone, Default)]
+pub struct AircraftInfo {
+    pub tail_number: String,
+    pub airline_and_flight_number: String,
+    pub atc_type_and_model: String,
+    pub title: String,
+}
+
+impl AircraftInfo {
+    /// The ICAO type designator. MSFS gives the ATC model as a localisation key such as
+    /// `TT:ATCCOM.AC_MODEL_B738.0.text`; older sims give the designator itself.
+    pub fn type_designator(&self) -> Option<String> {
+        let atc_model = self.atc_type_and_model.trim();
+        let designator = match atc_model.find("AC_MODEL_") {
+            Some(index) => atc_model[index + "AC_MODEL_".len()..].split(['.', '_']).next().unwrap_or_default(),
+            None => atc_model,
+        };
+        let is_designator = (2..=4).contains(&designator.len()) && designator.chars().all(|c| c.is_ascii_alphanumeric());
+        if is_designator { Some(designator.to_uppercase()) } else { None }
+    }
+
+    /// The radiotelephony callsign, being the airline name with the flight number dropped.
+    pub fn telephony(&self) -> Option<String> {
+        let airline = self.airline_and_flight_number.trim().trim_end_matches(|c: char| c.is_ascii_digit() || c.is_whitespace());
+        if airline.chars().any(|c| c.is_alphabetic()) { Some(airline.to_uppercase()) } else { None }
+    }
+
+    /// A name to give for the aircraft when a controller asks who it is.
+    pub fn display_name(&self) -> &str {
+        if !self.airline_and_flight_number.trim().is_empty() {
+            self.airline_and_flight_number.trim()
+        } else {
+            self.tail_number.trim()
+        }
+    }
+
+    fn set(&mut self, string_type: StringType, value: String) {
+        match string_type {
+            StringType::TailNumber => self.tail_number = value,
+            StringType::AirlineNameAndFlightNumber => self.airline_and_flight_number = value,
+            StringType::AtcAcTypeAndModel => self.atc_type_and_model = value,
+            StringType::AircraftTitle => self.title = value,
+            StringType::AtcAcTypeAndLastThreeOfTail => {},
+        }
+    }
+}
+
+
+/// Fetches `AircraftInfo` once per AI id without holding up the source thread.
+///
+/// FSUIPC answers one string request at a time, so requests are queued and one is kept in
+/// flight. Each call to `poll` collects whatever has arrived and sends the next request,
+/// for no longer than the time it is given.
+#[derive(Default)]
+pub struct EnrichmentCache {
+    entries: HashMap<u32, Entry>,
+    queue: VecDeque<u32>,
+    pending: Option<PendingString>,
+}
+
+#[derive(Default)]
+struct Entry {
+    info: AircraftInfo,
+    /// Index into `STRINGS` of the next string to fetch.
+    next: usize,
+}
+
+struct PendingString {
+    id: u32,
+    string_type: StringType,
+    timestamp: u32,
+    sent: Instant,
+}
+
+impl EnrichmentCache {
+    pub fn new() -> EnrichmentCache {
+        EnrichmentCache::default()
+    }
+
+    /// Queues an aircraft to be fetched, if it has not been already.
+    pub fn request(&mut self, id: u32) {
+        if let hash_map::Entry::Vacant(entry) = self.entries.entry(id) {
+            entry.insert(Entry::default());
+            self.queue.push_back(id);
+        }
+    }
+
+    /// The info for an aircraft, once all of it has been fetched.
+    pub fn get(&self, id: u32) -> Option<&AircraftInfo> {
+        self.entries.get(&id).filter(|entry| entry.next >= STRINGS.len()).map(|entry| &entry.info)
+    }
+
+    pub fn remove(&mut self, id: u32) {
+        self.entries.remove(&id);
+    }
+
+    pub fn poll(&mut self, budget: Duration) -> Result<(), fsuipc::Error> {
+        let started = Instant::now();
+        loop {
+            if let Some(pending) = self.pending.as_ref() {
+                let value = match fsuipc::poll_string(pending.timestamp)? {
+                    Some(value) => value,
+                    None if pending.sent.elapsed() > STRING_TIMEOUT => String::new(),
+                    None if started.elapsed() < budget => {
+                        thread::sleep(POLL_INTERVAL);
+                        continue;
+                    },
+                    None => return Ok(()),
+                };
+                if let Some(entry) = self.entries.get_mut(&pending.id) {
+                    entry.info.set(pending.string_type, value.trim().to_owned());
+                    entry.next += 1;
+                }
+                self.pending = None;
+            }
+
+            if started.elapsed() >= budget { return Ok(()) }
+            let (id, string_type) = match self.next_string() {
+                Some(next) => next,
+                None => return Ok(()),
+            };
+            let timestamp = fsuipc::request_string(id, string_type)?;
+            self.pending = Some(PendingString { id, string_type, timestamp, sent: Instant::now() });
+        }
+    }
+
+    fn next_string(&mut self) -> Option<(u32, StringType)> {
+        while let Some(&id) = self.queue.front() {
+            match self.entries.get(&id) {
+                Some(entry) if entry.next < STRINGS.len() => return Some((id, STRINGS[entry.next])),
+                _ => { self.queue.pop_front(); },
+            }
+        }
+        None
+    }
+}
+
+
+/// Fetched aircraft info by callsign, shared with the FSD server to answer client queries.
+#[derive(Clone, Default)]
+pub struct AircraftInfoProvider {
+    aircraft: Arc<Mutex<HashMap<String, AircraftInfo>>>,
+}
+
+impl AircraftInfoProvider {
+    pub fn new() -> AircraftInfoProvider {
+        AircraftInfoProvider::default()
+    }
+
+    pub fn insert(&self, callsign: &str, info: AircraftInfo) {
+        self.aircraft.lock().unwrap().insert(callsign.to_owned(), info);
+    }
+
+    pub fn get(&self, callsign: &str) -> Option<AircraftInfo> {
+        self.aircraft.lock().unwrap().get(callsign).cloned()
+    }
+
+    pub fn remove(&self, callsign: &str) {
+        self.aircraft.lock().unwrap().remove(callsign);
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::Display;
+
+use super::track::Track;
+
+const METRES_PER_SECOND_PER_KNOT: f64 = 0.514444;
+const METRES_PER_SECOND_PER_FPM: f64 = 0.00508;
+const METRES_PER_FOOT: f64 = 0.3048;
+
+
+
+/// The VATSIM velocity ("fast") position packet, `^`.
+///
+/// Velocities are in metres per second along east, up and north; angular rates are in radians
+/// per second. ATC clients that understand the packet use it to move targets smoothly between
+/// updates.
+#[derive(Debug, Clone)]
+pub struct FastPositionMessage {
+    pub callsign: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub true_alt_m: f64,
+    /// Height above the ground, which is left empty if the source doesn't know it.
+    pub agl_alt_m: Option<f64>,
+    pub pitch: f64,
+    pub bank: f64,
+    pub hdg: f64,
+    pub velocity_east: f64,
+    pub velocity_up: f64,
+    pub velocity_north: f64,
+    pub pitch_rate: f64,
+    pub heading_rate: f64,
+    pub bank_rate: f64,
+    pub nose_gear_angle: f64,
+}
+
+impl FastPositionMessage {
+    pub fn from_track(track: &Track, agl_alt_ft: Option<f64>) -> FastPositionMessage {
+        let sample = &track.latest;
+        let gs = sample.gs * METRES_PER_SECOND_PER_KNOT;
+        let hdg = sample.hdg.to_radians();
+        FastPositionMessage {
+            callsign: track.callsign.clone(),
+            lat: sample.lat,
+            lon: sample.lon,
+            true_alt_m: sample.true_alt * METRES_PER_FOOT,
+            agl_alt_m: agl_alt_ft.map(|agl_alt_ft| agl_alt_ft * METRES_PER_FOOT),
+            pitch: sample.pitch,
+            bank: sample.bank,
+            hdg: sample.hdg,
+            velocity_east: gs * hdg.sin(),
+            velocity_up: sample.vs * METRES_PER_SECOND_PER_FPM,
+            velocity_north: gs * hdg.cos(),
+            pitch_rate: track.pitch_rate().to_radians(),
+            heading_rate: track.heading_rate().to_radians(),
+            bank_rate: track.bank_rate().to_radians(),
+            nose_gear_angle: 0.0,
+        }
+    }
+}
+
+impl Display for FastPositionMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let agl_alt_m = self.agl_alt_m.map(|agl_alt_m| format!("{:.2}", agl_alt_m)).unwrap_or_default();
+        write!(f, "^{}:{:.7}:{:.7}:{:.2}:{}:{}:{:.4}:{:.4}:{:.4}:{:.4}:{:.4}:{:.4}:{:.2}",
+            self.callsign, self.lat, self.lon, self.true_alt_m, agl_alt_m, encode_pitch_bank_heading(self.pitch, self.bank, self.hdg),
+            self.velocity_east, self.velocity_up, self.velocity_north, self.pitch_rate, self.heading_rate, self.bank_rate, self.nose_gear_angle)
+    }
+}
+
+/// Packs attitude into the 32-bit FSD format: ten bits each of pitch, bank and heading in
+/// 1024ths of a turn, with pitch and bank negated.
+pub fn encode_pitch_bank_heading(pitch: f64, bank: f64, hdg: f64) -> u32 {
+    let encode = |degrees: f64| ((degrees / 360.0 * 1024.0).round() as i32 as u32) & 0x3FF;
+    (encode(-pitch) << 22) | (encode(-bank) << 12) | (encode(hdg.rem_euclid(360.0)) << 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::core::track::TrackSample;
+
+    fn track() -> Track {
+        Track::new(String::from("BAW123"), TrackSample {
+            lat: 51.5, lon: -0.5, true_alt: 3000.0, pressure_alt: 3100.0, gs: 200.0, hdg: 90.0,
+            vs: 1000.0, pitch: 0.0, bank: 0.0, on_ground: false, time: Instant::now(),
+        })
+    }
+
+    #[test]
+    fn sends_known_height_above_ground() {
+        let message = FastPositionMessage::from_track(&track(), Some(2500.0)).to_string();
+        let fields: Vec<&str> = message.split(':').collect();
+        assert_eq!(fields[0], "^BAW123");
+        assert_eq!(fields[3], "914.40");
+        assert_eq!(fields[4], "762.00");
+        let velocity_east: f64 = fields[6].parse().unwrap();
+        assert!((velocity_east - 102.89).abs() < 0.01);
+    }
+
+    #[test]
+    fn leaves_unknown_height_above_ground_empty() {
+        let message = FastPositionMessage::from_track(&track(), None).to_string();
+        assert_eq!(message.split(':').nth(4), Some(""));
+        assert_eq!(message.split(':').count(), 13);
+    }
+}
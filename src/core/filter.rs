@@ -0,0 +1,291 @@
+use super::{airports::AirportDatabase, config::Config, geo, util::glob_match};
+
+const DEFAULT_RADIUS_NM: f64 = 40.0;
+
+
+
+/// Which part of the world traffic is shown from.
+#[derive(Debug, Clone, Default)]
+pub enum AreaFilter {
+    #[default]
+    Everywhere,
+    AroundOwnAircraft { radius_nm: f64 },
+    AroundAirport { icao: String, radius_nm: f64 },
+    /// Inside a polygon of (lat, lon) vertices.
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl AreaFilter {
+    /// Reads the `[area]` section, for example `mode = airport`, `airport = EGLL`, `radius = 25`,
+    /// or `mode = polygon` with `polygon = 51.2 -1.0, 51.8 -1.0, 51.8 0.5, 51.2 0.5`.
+    pub fn from_config(config: &Config) -> AreaFilter {
+        let radius_nm = config.get_parsed("area", "radius").unwrap_or(DEFAULT_RADIUS_NM);
+        match config.get("area", "mode").map(|mode| mode.to_lowercase()).as_deref() {
+            Some("own") => AreaFilter::AroundOwnAircraft { radius_nm },
+            Some("airport") => match config.get("area", "airport") {
+                Some(icao) => AreaFilter::AroundAirport { icao: icao.to_uppercase(), radius_nm },
+                None => AreaFilter::Everywhere,
+            },
+            Some("polygon") => {
+                let vertices = config.get("area", "polygon").map(parse_polygon).unwrap_or_default();
+                if vertices.len() >= 3 { AreaFilter::Polygon(vertices) } else { AreaFilter::Everywhere }
+            },
+            _ => AreaFilter::Everywhere,
+        }
+    }
+
+    /// The radius to limit the sim's own TCAS tables to, which are centred on our aircraft.
+    pub fn tcas_range_nm(&self) -> u8 {
+        match self {
+            AreaFilter::AroundOwnAircraft { radius_nm } => radius_nm.ceil().clamp(1.0, u8::MAX as f64) as u8,
+            _ => 0,
+        }
+    }
+
+    /// Works out where the area is right now. Areas whose centre is not known yet let
+    /// everything through rather than nothing.
+    pub fn resolve(&self, own_position: Option<(f64, f64)>, airports: &AirportDatabase) -> Area {
+        match self {
+            AreaFilter::Everywhere => Area::Everywhere,
+            AreaFilter::AroundOwnAircraft { radius_nm } => match own_position {
+                Some((lat, lon)) => Area::Circle { lat, lon, radius_nm: *radius_nm },
+                None => Area::Everywhere,
+            },
+            AreaFilter::AroundAirport { icao, radius_nm } => match airports.get(icao) {
+                Some(airport) => Area::Circle { lat: airport.lat, lon: airport.lon, radius_nm: *radius_nm },
+                None => Area::Everywhere,
+            },
+            AreaFilter::Polygon(vertices) => Area::Polygon(vertices.clone()),
+        }
+    }
+}
+
+/// Which aircraft are shown by whether they are on the ground.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroundFilter {
+    #[default]
+    All,
+    GroundOnly,
+    AirborneOnly,
+}
+
+/// Limits traffic to the ground, the air, or a band of pressure altitudes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LevelFilter {
+    pub ground: GroundFilter,
+    pub min_altitude_ft: Option<f64>,
+    pub max_altitude_ft: Option<f64>,
+}
+
+impl LevelFilter {
+    /// Reads `traffic = ground|airborne|all`, `min_altitude` and `max_altitude` from `[filters]`.
+    /// Altitudes are in feet or given as flight levels, such as `FL245`.
+    pub fn from_config(config: &Config) -> LevelFilter {
+        let ground = match config.get("filters", "traffic").map(|traffic| traffic.to_lowercase()).as_deref() {
+            Some("ground") => GroundFilter::GroundOnly,
+            Some("airborne") => GroundFilter::AirborneOnly,
+            _ => GroundFilter::All,
+        };
+        LevelFilter {
+            ground,
+            min_altitude_ft: config.get("filters", "min_altitude").and_then(parse_altitude),
+            max_altitude_ft: config.get("filters", "max_altitude").and_then(parse_altitude),
+        }
+    }
+
+    /// Whether anything in the sim's ground (or airborne) TCAS table could get through.
+    pub fn may_include(&self, on_ground: bool) -> bool {
+        match self.ground {
+            GroundFilter::All => true,
+            GroundFilter::GroundOnly => on_ground,
+            GroundFilter::AirborneOnly => !on_ground,
+        }
+    }
+
+    pub fn allows(&self, on_ground: bool, pressure_alt: f64) -> bool {
+        self.may_include(on_ground)
+            && self.min_altitude_ft.is_none_or(|min| pressure_alt >= min)
+            && self.max_altitude_ft.is_none_or(|max| pressure_alt <= max)
+    }
+}
+
+/// Callsign patterns to show and hide. Patterns are globs, and several can be given
+/// separated by `|`, as in `BAW*|EZY*`.
+#[derive(Debug, Clone, Default)]
+pub struct CallsignFilter {
+    /// If any are given, only callsigns matching one of them are shown.
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl CallsignFilter {
+    /// Reads `include` and `exclude` from `[filters]`.
+    pub fn from_config(config: &Config) -> CallsignFilter {
+        CallsignFilter {
+            include: config.get("filters", "include").map(parse_patterns).unwrap_or_default(),
+            exclude: config.get("filters", "exclude").map(parse_patterns).unwrap_or_default(),
+        }
+    }
+
+    pub fn allows(&self, callsign: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|pattern| glob_match(pattern, callsign));
+        included && !self.exclude.iter().any(|pattern| glob_match(pattern, callsign))
+    }
+}
+
+pub enum Area {
+    Everywhere,
+    Circle { lat: f64, lon: f64, radius_nm: f64 },
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Area {
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        match self {
+            Area::Everywhere => true,
+            Area::Circle { lat: centre_lat, lon: centre_lon, radius_nm } => geo::distance_nm(*centre_lat, *centre_lon, lat, lon) <= *radius_nm,
+            Area::Polygon(vertices) => polygon_contains(vertices, lat, lon),
+        }
+    }
+}
+
+/// Even-odd ray casting, treating lat/lon as flat. Fine for sector-sized polygons away from
+/// the poles and the antimeridian.
+fn polygon_contains(vertices: &[(f64, f64)], lat: f64, lon: f64) -> bool {
+    let mut inside = false;
+    let mut previous = match vertices.last() {
+        Some(vertex) => *vertex,
+        None => return false,
+    };
+    for &vertex in vertices {
+        let ((lat_a, lon_a), (lat_b, lon_b)) = (previous, vertex);
+        if (lat_a > lat) != (lat_b > lat) && lon < lon_a + (lat - lat_a) / (lat_b - lat_a) * (lon_b - lon_a) {
+            inside = !inside;
+        }
+        previous = vertex;
+    }
+    inside
+}
+
+fn parse_patterns(text: &str) -> Vec<String> {
+    text.split(|c: char| c == '|' || c == ',' || c.is_whitespace()).filter(|pattern| !pattern.is_empty()).map(|pattern| pattern.to_owned()).collect()
+}
+
+fn parse_altitude(text: &str) -> Option<f64> {
+    let text = text.trim().to_uppercase();
+    match text.strip_prefix("FL") {
+        Some(level) => level.trim().parse::<f64>().ok().map(|level| level * 100.0),
+        None => text.parse().ok(),
+    }
+}
+
+/// Parses comma-separated `lat lon` pairs.
+fn parse_polygon(text: &str) -> Vec<(f64, f64)> {
+    text.split(',').filter_map(|pair| {
+        let mut coords = pair.split_whitespace().map(|coord| coord.parse::<f64>());
+        match (coords.next(), coords.next()) {
+            (Some(Ok(lat)), Some(Ok(lon))) => Some((lat, lon)),
+            _ => None,
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Roughly the London TMA, with a notch cut out of the east side.
+    const NOTCHED: [(f64, f64); 6] = [(51.0, -1.0), (52.0, -1.0), (52.0, 1.0), (51.6, 1.0), (51.6, 0.0), (51.0, 0.0)];
+
+    #[test]
+    fn polygon_contains_points_inside_only() {
+        assert!(polygon_contains(&NOTCHED, 51.5, -0.5));
+        assert!(polygon_contains(&NOTCHED, 51.8, 0.5));
+        // In the notch
+        assert!(!polygon_contains(&NOTCHED, 51.3, 0.5));
+        assert!(!polygon_contains(&NOTCHED, 50.9, -0.5));
+        assert!(!polygon_contains(&NOTCHED, 51.5, -1.1));
+        assert!(!polygon_contains(&[], 51.5, -0.5));
+    }
+
+    #[test]
+    fn polygon_area_from_config() {
+        let config = Config::parse("[area]\nmode = polygon\npolygon = 51.0 -1.0, 52.0 -1.0, 52.0 1.0, 51.6 1.0, 51.6 0.0, 51.0 0.0\n");
+        let area = AreaFilter::from_config(&config).resolve(None, &AirportDatabase::new());
+        assert!(area.contains(51.5, -0.5));
+        assert!(!area.contains(51.3, 0.5));
+
+        // Too few vertices to enclose anything
+        let config = Config::parse("[area]\nmode = polygon\npolygon = 51.0 -1.0, 52.0 -1.0\n");
+        assert!(matches!(AreaFilter::from_config(&config), AreaFilter::Everywhere));
+    }
+
+    #[test]
+    fn level_filter_ground_and_airborne_only() {
+        let ground_only = LevelFilter::from_config(&Config::parse("[filters]
+traffic = Ground
+"));
+        assert!(ground_only.allows(true, 80.0));
+        assert!(!ground_only.allows(false, 80.0));
+        assert!(!ground_only.may_include(false));
+
+        let airborne_only = LevelFilter::from_config(&Config::parse("[filters]
+traffic = airborne
+"));
+        assert!(!airborne_only.allows(true, 80.0));
+        assert!(airborne_only.allows(false, 80.0));
+
+        let all = LevelFilter::from_config(&Config::default());
+        assert!(all.allows(true, 80.0) && all.allows(false, 35000.0));
+    }
+
+    #[test]
+    fn level_filter_bounds_are_inclusive() {
+        let filter = LevelFilter::from_config(&Config::parse("[filters]
+min_altitude = 3000
+max_altitude = fl245
+"));
+        assert_eq!(filter.min_altitude_ft, Some(3000.0));
+        assert_eq!(filter.max_altitude_ft, Some(24500.0));
+        assert!(filter.allows(false, 3000.0));
+        assert!(filter.allows(false, 24500.0));
+        assert!(!filter.allows(false, 2999.0));
+        assert!(!filter.allows(false, 24600.0));
+        // Aircraft on the ground at an airport below the band are filtered out too
+        assert!(!filter.allows(true, 80.0));
+    }
+
+    #[test]
+    fn parses_flight_levels_and_feet() {
+        assert_eq!(parse_altitude("FL245"), Some(24500.0));
+        assert_eq!(parse_altitude(" fl 100 "), Some(10000.0));
+        assert_eq!(parse_altitude("4500"), Some(4500.0));
+        assert_eq!(parse_altitude("FL"), None);
+        assert_eq!(parse_altitude("high"), None);
+    }
+
+    #[test]
+    fn callsign_filter_includes_then_excludes() {
+        let filter = CallsignFilter::from_config(&Config::parse("[filters]
+include = BAW*|EZY*
+exclude = BAW9?? EZY123
+"));
+        assert_eq!(filter.include, ["BAW*", "EZY*"]);
+        assert!(filter.allows("BAW123"));
+        assert!(filter.allows("EZY45"));
+        assert!(filter.allows("baw123"));
+        assert!(!filter.allows("BAW901"));
+        assert!(!filter.allows("EZY123"));
+        assert!(!filter.allows("RYR1"));
+    }
+
+    #[test]
+    fn empty_callsign_filter_allows_everything() {
+        assert!(CallsignFilter::default().allows("ANY1"));
+        let exclude_only = CallsignFilter::from_config(&Config::parse("[filters]
+exclude = *TEST*
+"));
+        assert!(exclude_only.allows("DLH4"));
+        assert!(!exclude_only.allows("GTEST1"));
+    }
+}
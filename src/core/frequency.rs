@@ -0,0 +1,82 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr, sync::{Arc, Mutex}};
+
+/// Channels closer together than this are taken to be the same, since the sim only gives the
+/// first two decimal places and 8.33 kHz channel names don't match their actual frequency.
+const CHANNEL_TOLERANCE_KHZ: u32 = 10;
+/// The VHF airband used for COM radios.
+const AIRBAND_KHZ: std::ops::RangeInclusive<u32> = 118_000..=136_990;
+
+
+
+/// A VHF radio frequency, in kHz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frequency(pub u32);
+
+impl Frequency {
+    /// Decodes the sim's COM frequency format, four BCD digits without the leading 1, so
+    /// 118.50 is `0x1850`.
+    pub fn from_bcd(bcd: u16) -> Option<Frequency> {
+        let digits = format!("{:04X}", bcd).parse::<u32>().ok()?;
+        Frequency::in_airband(100_000 + digits * 10)
+    }
+
+    /// Parses the frequency part of an FSD address, `@18500` for 118.500.
+    pub fn from_fsd(address: &str) -> Option<Frequency> {
+        let digits = address.strip_prefix('@')?;
+        if digits.len() != 5 { return None; }
+        Frequency::in_airband(100_000 + digits.parse::<u32>().ok()?)
+    }
+
+    fn in_airband(khz: u32) -> Option<Frequency> {
+        if AIRBAND_KHZ.contains(&khz) { Some(Frequency(khz)) } else { None }
+    }
+
+    pub fn matches(&self, other: Frequency) -> bool {
+        self.0.abs_diff(other.0) < CHANNEL_TOLERANCE_KHZ
+    }
+}
+
+impl FromStr for Frequency {
+    type Err = ();
+
+    /// Parses a frequency in MHz, such as `118.5` or `118.500`.
+    fn from_str(s: &str) -> Result<Frequency, ()> {
+        let (mhz, decimals) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if decimals.len() > 3 || !decimals.chars().all(|c| c.is_ascii_digit()) { return Err(()); }
+        let mhz = mhz.parse::<u32>().map_err(|_| ())?;
+        let khz = format!("{:0<3}", decimals).parse::<u32>().map_err(|_| ())?;
+        Frequency::in_airband(mhz * 1000 + khz).ok_or(())
+    }
+}
+
+impl Display for Frequency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:03}", self.0 / 1000, self.0 % 1000)
+    }
+}
+
+
+/// The COM1 frequency of each aircraft by callsign, shared with the FSD server to answer
+/// frequency queries.
+#[derive(Clone, Default)]
+pub struct FrequencyProvider {
+    frequencies: Arc<Mutex<HashMap<String, Frequency>>>,
+}
+
+impl FrequencyProvider {
+    pub fn new() -> FrequencyProvider {
+        FrequencyProvider::default()
+    }
+
+    pub fn set(&self, callsign: &str, frequency: Option<Frequency>) {
+        let mut frequencies = self.frequencies.lock().unwrap();
+        match frequency {
+            Some(frequency) => { frequencies.insert(callsign.to_owned(), frequency); },
+            None => { frequencies.remove(callsign); },
+        }
+    }
+
+    pub fn get(&self, callsign: &str) -> Option<Frequency> {
+        self.frequencies.lock().unwrap().get(callsign).copied()
+    }
+}
@@ -1,10 +1,10 @@
 use std::{io::{BufRead, BufReader, ErrorKind, LineWriter, Write}, net::{TcpListener, TcpStream}, sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, Sender, TryRecvError}, Arc}, thread::{self, JoinHandle}, time::Duration};
 
-use fsd_interface::{messages::{ClientQueryMessage, ClientQueryResponseMessage, FlightPlanMessage, MetarResponseMessage, TextMessage}, ClientQueryType, FsdMessageType};
+use fsd_interface::{messages::{ClientQueryResponseMessage, FlightPlanMessage, MetarResponseMessage, TextMessage}, ClientQueryType, FsdMessageType};
 
 use crate::ui::{Message, Ui};
 
-use super::{metar::MetarProvider, vatsim::VatsimDataProvider, worker::FLIGHT_PLAN_RECIPIENT, Preferences};
+use super::{atis::AtisProvider, frequency::Frequency, source::SimCommand, Shared};
 
 const SERVER_CALLSIGN: &str = "SERVER";
 const WELCOME_MESSAGE: &str = "Connected to Traffic Viewer. Welcome!";
@@ -16,10 +16,10 @@ pub struct Server {
 }
 
 impl Server {
-    pub fn new<U: Ui + 'static>(preferences: Preferences, vatsim_data_provider: VatsimDataProvider, metar_provider: MetarProvider, ui: U, should_terminate: Arc<AtomicBool>) -> Server {
+    pub fn new<U: Ui + 'static>(shared: Shared, sim_commands: Sender<SimCommand>, ui: U, should_terminate: Arc<AtomicBool>) -> Server {
         let u = ui.clone();
         let (tx, rx) = mpsc::channel();
-        let thread = Some(server_thread(Arc::clone(&should_terminate), vatsim_data_provider, metar_provider, preferences, u, rx));
+        let thread = Some(server_thread(Arc::clone(&should_terminate), shared, sim_commands, u, rx));
         Server {
             thread,
             should_terminate,
@@ -27,10 +27,6 @@ impl Server {
         }
     }
 
-    pub fn send_packet(&mut self, message: String) {
-        self.sender.send(message);
-    }
-
     pub fn sender(&self) -> Sender<String> {
         self.sender.clone()
     }
@@ -45,8 +41,9 @@ impl Drop for Server {
     }
 }
 
-fn server_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, vatsim_data_provider: VatsimDataProvider, metar_provider: MetarProvider, preferences: Preferences, ui: U, receiver: Receiver<String>) -> JoinHandle<()> {
+fn server_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, shared: Shared, sim_commands: Sender<SimCommand>, ui: U, receiver: Receiver<String>) -> JoinHandle<()> {
     thread::Builder::new().name("TrafficViewerFSDThread".into()).spawn(move|| {
+        let atis_provider = AtisProvider::new(shared.metar_provider.clone(), shared.vatsim_data_provider.clone(), shared.preferences.clone());
         let tcp_listener = match TcpListener::bind("127.0.0.1:6809") {
             Ok(tcp_listener) => tcp_listener,
             Err(_) => {
@@ -63,14 +60,14 @@ fn server_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, vatsim_data
                 Ok((stream, _)) => {
                     stream.set_nonblocking(false).ok();
                     let mut writer = LineWriter::new(stream.try_clone().unwrap());
-                    while let Ok(_) = receiver.try_recv() {}
+                    while receiver.try_recv().is_ok() {}
                     let this_connection_ended = Arc::new(AtomicBool::new(false));
                     // Spawn recv thread
-                    let recv_thread = recv_thread(Arc::clone(&should_terminate), Arc::clone(&this_connection_ended), stream, vatsim_data_provider.clone(), metar_provider.clone(), preferences.clone(), ui.clone());
+                    let recv_thread = recv_thread(Arc::clone(&should_terminate), Arc::clone(&this_connection_ended), stream, shared.clone(), atis_provider.clone(), sim_commands.clone(), ui.clone());
                     while !should_terminate.load(Ordering::Relaxed) && !this_connection_ended.load(Ordering::Relaxed) {
                         match receiver.try_recv() {
-                            Ok(msg) => _ = {
-                                writer.write(&string_to_byte_slice(&format!("{}\r\n", msg))).ok();
+                            Ok(msg) => {
+                                writer.write_all(&string_to_byte_slice(&format!("{}\r\n", msg))).ok();
                             },
                             Err(TryRecvError::Disconnected) => {
                                 break;
@@ -98,7 +95,8 @@ fn server_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, vatsim_data
 }
 
 
-fn recv_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, this_connection_closed: Arc<AtomicBool>, tcp_stream: TcpStream, vatsim_data_provider: VatsimDataProvider, metar_provider: MetarProvider, mut preferences: Preferences, ui: U) -> JoinHandle<()> {
+fn recv_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, this_connection_closed: Arc<AtomicBool>, tcp_stream: TcpStream, shared: Shared, atis_provider: AtisProvider, sim_commands: Sender<SimCommand>, ui: U) -> JoinHandle<()> {
+    let Shared { mut preferences, metar_provider, vatsim_data_provider, sim_flight_plan_provider, aircraft_info_provider, frequency_provider, .. } = shared;
     preferences.set_es_callsign(String::new());
     thread::Builder::new().name(String::from("TrafficViewerFSDRecvThread")).spawn(move|| {
         let mut writer = LineWriter::new(tcp_stream.try_clone().unwrap());
@@ -114,35 +112,59 @@ fn recv_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, this_connecti
                 },
                 Ok(_) => {
                     let message = byte_slice_to_string(&buffer);
+                    if let Some((subject, code)) = parse_squawk_assignment(message.trim()) {
+                        if subject == preferences.pilot_callsign() && preferences.write_assigned_squawk() {
+                            sim_commands.send(SimCommand::SetSquawk(code)).ok();
+                        }
+                        continue;
+                    }
                     if let Ok(fsd_message) = fsd_interface::parse_message(message.trim()) {
                         match fsd_message {
                             FsdMessageType::AtcRegisterMessage(msg) => {
                                 preferences.set_es_callsign(msg.from.clone());
                                 ui.dispatch_message(Message::EuroscopeConnected(msg.from.clone()));
-                                writer.write(&string_to_byte_slice(&format!("{}\r\n", TextMessage::new(SERVER_CALLSIGN, msg.from, WELCOME_MESSAGE)))).ok();
+                                writer.write_all(&string_to_byte_slice(&format!("{}\r\n", TextMessage::new(SERVER_CALLSIGN, msg.from, WELCOME_MESSAGE)))).ok();
                             },
                             FsdMessageType::MetarRequestMessage(msg) => {
                                 println!("METAR requested for {}", msg.station);
                                 if let Some(metar) = metar_provider.lookup_metar(&msg.station) {
                                     let response = format!("{}\r\n", MetarResponseMessage::new(SERVER_CALLSIGN, msg.from, metar));
                                     println!("Sending {}", response);
-                                    writer.write(&string_to_byte_slice(&response)).ok();
+                                    writer.write_all(&string_to_byte_slice(&response)).ok();
                                 }
                             },
+                            FsdMessageType::TextMessage(msg) => {
+                                sim_commands.send(SimCommand::ShowTextMessage { from: msg.from, to: msg.to, message: msg.message }).ok();
+                            },
                             FsdMessageType::ClientQueryMessage(cqm) => match cqm.query_type {
                                 ClientQueryType::RealName => {
-                                    if let Some(details) = vatsim_data_provider.get_aircraft_details(&cqm.to) {
-                                        let real_name = details.name;
-                                        let message = ClientQueryResponseMessage::real_name(cqm.to, cqm.from, real_name, String::new(), 1);
+                                    let real_name = vatsim_data_provider.get_aircraft_details(&cqm.to).map(|details| (details.name, String::new()))
+                                        .or_else(|| aircraft_info_provider.get(&cqm.to).map(|info| (info.display_name().to_owned(), info.title)));
+                                    if let Some((real_name, info)) = real_name {
+                                        let message = ClientQueryResponseMessage::real_name(cqm.to, cqm.from, real_name, info, 1);
                                         let response = format!("{}\r\n", message);
-                                        writer.write(&string_to_byte_slice(&response)).ok();
+                                        writer.write_all(&string_to_byte_slice(&response)).ok();
                                     }
                                 },
                                 ClientQueryType::FlightPlan(subject) => {
-                                    if let Some(flight_plan) = vatsim_data_provider.get_aircraft_details(&subject).and_then(|details| details.flight_plan).map(fsd_interface::FlightPlan::from) {
+                                    let flight_plan = vatsim_data_provider.get_aircraft_details(&subject).and_then(|details| details.flight_plan).map(fsd_interface::FlightPlan::from)
+                                        .or_else(|| sim_flight_plan_provider.get(&subject).map(fsd_interface::FlightPlan::from));
+                                    if let Some(flight_plan) = flight_plan {
                                         let message = FlightPlanMessage::new(cqm.from, subject, flight_plan);
                                         let response = format!("{}\r\n", message);
-                                        writer.write(&string_to_byte_slice(&response)).ok();
+                                        writer.write_all(&string_to_byte_slice(&response)).ok();
+                                    }
+                                },
+                                ClientQueryType::Com1Freq => {
+                                    if let Some(frequency) = frequency_provider.get(&cqm.to) {
+                                        writer.write_all(&string_to_byte_slice(&com1_response(&cqm.to, &cqm.from, frequency))).ok();
+                                    }
+                                },
+                                ClientQueryType::ATIS => {
+                                    if let Some(atis_lines) = atis_provider.get_atis(&cqm.to) {
+                                        for response in atis_response(&cqm.to, &cqm.from, &atis_lines) {
+                                            writer.write_all(&string_to_byte_slice(&response)).ok();
+                                        }
                                     }
                                 },
                                 _ => {},
@@ -164,6 +186,27 @@ fn recv_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, this_connecti
 }
 
 
+/// Picks the callsign and code out of a squawk assignment, `$CQ<from>:<to>:BC:<callsign>:<code>`.
+fn parse_squawk_assignment(message: &str) -> Option<(String, u16)> {
+    let fields: Vec<&str> = message.strip_prefix("$CQ")?.split(':').collect();
+    match fields.as_slice() {
+        [_, _, "BC", subject, code, ..] if code.len() == 4 && code.chars().all(|c| ('0'..='7').contains(&c)) => {
+            Some((subject.to_string(), code.parse().ok()?))
+        },
+        _ => None,
+    }
+}
+
+fn com1_response(callsign: &str, requester: &str, frequency: Frequency) -> String {
+    format!("$CR{}:{}:C?:{}\r\n", callsign, requester, frequency)
+}
+
+/// Builds the `$CR` ATIS reply: one `T` packet per line of text, then an `E` packet carrying the line count.
+fn atis_response(station: &str, requester: &str, lines: &[String]) -> Vec<String> {
+    let mut packets: Vec<String> = lines.iter().map(|line| format!("$CR{}:{}:ATIS:T:{}\r\n", station, requester, line.replace(':', " "))).collect();
+    packets.push(format!("$CR{}:{}:ATIS:E:{}\r\n", station, requester, lines.len()));
+    packets
+}
 
 #[inline]
 fn byte_slice_to_string(slice: &[u8]) -> String {
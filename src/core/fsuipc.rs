@@ -1,10 +1,14 @@
 
-use std::{ffi::{c_void, CStr}, mem::{self, MaybeUninit}, thread};
+use std::{collections::HashMap, ffi::{c_void, CStr}, mem::{self, MaybeUninit}};
 
+use super::{altimetry, frequency::Frequency};
 
+
+#[cfg(windows)]
 #[link(name = "User32", kind="dylib")]
-extern {}
+extern "C" {}
 
+#[cfg(windows)]
 #[allow(unused)]
 #[link(name = "FSUIPCuser64")]
 extern "C" {
@@ -19,19 +23,49 @@ extern "C" {
     pub static FSUIPC_Lib_Version: u32;
 }
 
+#[cfg(not(windows))]
+use self::unavailable::*;
+
+/// FSUIPC only exists on Windows. Elsewhere there is never a sim to link to.
+#[cfg(not(windows))]
+#[allow(non_snake_case, non_upper_case_globals)]
+mod unavailable {
+    use super::{c_void, Error, FlightSimVersion};
+
+    pub unsafe fn FSUIPC_Open(_fs_req: u32, result: *mut u32) -> i32 {
+        *result = Error::NoSimConnection as u32;
+        0
+    }
+    pub unsafe fn FSUIPC_Read(_offset: u32, _size: u32, _destination: *mut c_void, result: *mut u32) -> i32 {
+        *result = Error::NotOpen as u32;
+        0
+    }
+    pub unsafe fn FSUIPC_Write(_offset: u32, _size: u32, _source: *const c_void, result: *mut u32) -> i32 {
+        *result = Error::NotOpen as u32;
+        0
+    }
+    pub unsafe fn FSUIPC_Process(result: *mut u32) -> i32 {
+        *result = Error::NotOpen as u32;
+        0
+    }
+    pub static FSUIPC_Version: u32 = 0;
+    pub static FSUIPC_FS_Version: FlightSimVersion = FlightSimVersion::FS98;
+    pub static FSUIPC_Lib_Version: u32 = 0;
+}
+
 pub fn read<T>(offset: u32) -> Result<T, Error> {
     unsafe {
         let mut result = 0;
         let mut destination: MaybeUninit<T> = MaybeUninit::uninit(); 
         let size = mem::size_of::<T>() as u32;
         if FSUIPC_Read(offset, size, destination.as_mut_ptr() as *mut _, &mut result) != 1 || FSUIPC_Process(&mut result) != 1 {
-            let result = mem::transmute(result);
-            return Err(result);
+            return Err(mem::transmute::<u32, Error>(result));
         }
-        return Ok(destination.assume_init());
+        Ok(destination.assume_init())
     }
 }
 
+#[allow(unused)]
 #[derive(Debug, Clone)]
 pub struct Versions {
     pub fsuipc_version: String,
@@ -47,7 +81,7 @@ pub fn link(required_fs_version: Option<FlightSimVersion>) -> Result<Versions, E
             None => 0,
         };
         if FSUIPC_Open(fs_req, &mut result) != 1 {
-            return Err(mem::transmute(result));
+            return Err(mem::transmute::<u32, Error>(result));
         }
 
         let v_a: u8 = 48 + (0x0f & (FSUIPC_Version >> 28)) as u8;
@@ -64,13 +98,13 @@ pub fn link(required_fs_version: Option<FlightSimVersion>) -> Result<Versions, E
         if v_e != 32 {
             fsuipc_version.push(char::from_u32_unchecked(v_e as u32));
         }
-        set_preferences()?;
-        return Ok(Versions { fsuipc_version: fsuipc_version, fs_version: FSUIPC_FS_Version, fsuipc_lib_version: FSUIPC_Lib_Version })
+        set_preferences(0)?;
+        Ok(Versions { fsuipc_version, fs_version: FSUIPC_FS_Version, fsuipc_lib_version: FSUIPC_Lib_Version })
     }
 }
 
 
-#[allow(unused)]
+#[allow(unused, clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, Copy)]
 #[repr(u32)]
 pub enum FlightSimVersion {
@@ -184,7 +218,77 @@ pub struct TcasData {
     pub com1: u16,
 }
 
+impl TcasData {
+    /// Whether the aircraft is on the ground, going by its AI state if it has one and otherwise
+    /// by which of the TCAS tables it was read from.
+    pub fn on_ground(&self, from_ground_table: bool) -> bool {
+        match State::try_from(self.state) {
+            Ok(state) => state.is_on_ground(),
+            Err(_) => from_ground_table,
+        }
+    }
+}
+
+/// Departure and destination ICAOs of AI traffic, one 8-byte entry per slot in the same
+/// order as the TCAS tables.
+const AI_AIRPORTS_GROUND_TABLE: u32 = 0x1F80;
+const AI_AIRPORTS_AIRBORNE_TABLE: u32 = 0x2080;
+
+#[derive(Debug, Clone, Default)]
+pub struct AiRoute {
+    pub departure: Option<String>,
+    pub destination: Option<String>,
+}
+
+/// The departure and destination of each AI aircraft in one of the TCAS tables, keyed by id.
+pub fn get_ai_routes(on_ground: bool) -> Result<HashMap<u32, AiRoute>, Error> {
+    let (count_offset, tcas_offset, airports_offset) = if on_ground {
+        (0xE004, 0xE080, AI_AIRPORTS_GROUND_TABLE)
+    } else {
+        (0xF004, 0xF080, AI_AIRPORTS_AIRBORNE_TABLE)
+    };
+    let num_aircraft = read::<u16>(count_offset)? as usize;
+    let mut ids = vec![0_u32; num_aircraft];
+    let mut airports = vec![[0_u8; 8]; num_aircraft];
+    if num_aircraft == 0 {
+        return Ok(HashMap::new());
+    }
+    unsafe {
+        let mut result = 0;
+        for i in 0..num_aircraft {
+            let id_offset = (tcas_offset + i * mem::size_of::<TcasData>()) as u32;
+            if FSUIPC_Read(id_offset, mem::size_of::<u32>() as u32, &mut ids[i] as *mut u32 as *mut _, &mut result) != 1 {
+                let result: Error = mem::transmute(result);
+                return Err(result);
+            }
+            if FSUIPC_Read(airports_offset + i as u32 * 8, 8, airports[i].as_mut_ptr() as *mut _, &mut result) != 1 {
+                let result: Error = mem::transmute(result);
+                return Err(result);
+            }
+        }
+        if FSUIPC_Process(&mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+    }
+
+    Ok(ids.into_iter().zip(airports).filter(|(id, _)| *id != 0).map(|(id, airports)| {
+        let route = AiRoute {
+            departure: icao_from_bytes(&airports[..4]),
+            destination: icao_from_bytes(&airports[4..]),
+        };
+        (id, route)
+    }).collect())
+}
+
+fn icao_from_bytes(bytes: &[u8]) -> Option<String> {
+    let icao: String = bytes.iter().take_while(|b| **b != 0).map(|b| *b as char).collect();
+    let icao = icao.trim();
+    if icao.is_empty() { None } else { Some(icao.to_uppercase()) }
+}
+
 #[allow(unused)]
+#[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 enum State {
     Initialising = 128,
@@ -207,18 +311,35 @@ enum State {
     TaxiingIn,
     ShuttingDown,
 }
+impl State {
+    fn is_on_ground(&self) -> bool {
+        !matches!(self, State::Departing | State::Enroute | State::InCircuit | State::Landing | State::GoingAround)
+    }
+}
+impl TryFrom<u8> for State {
+    type Error = ();
+    fn try_from(value: u8) -> Result<State, ()> {
+        if (State::Initialising as u8..=State::ShuttingDown as u8).contains(&value) {
+            Ok(unsafe { mem::transmute::<u8, State>(value) })
+        } else {
+            Err(())
+        }
+    }
+}
 
-fn set_preferences() -> Result<(), Error> {
+/// Sets up both TCAS tables. A `range_nm` of 0 means no limit.
+fn set_preferences(range_nm: u8) -> Result<(), Error> {
     unsafe {
         let (range_a_offset, range_b_offset, tcas_id_option_offset) = (0xE068, 0xE069, 0xE06A);
-        let range = 0_u8;
+        let range = range_nm;
+        let no_limit = 0_u8;
         let mut result = 0;
         if FSUIPC_Write(range_a_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
 
-        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
+        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &no_limit as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
@@ -230,14 +351,13 @@ fn set_preferences() -> Result<(), Error> {
         }
 
         let (range_a_offset, range_b_offset, tcas_id_option_offset) = (0xF068, 0xF069, 0xF06A);
-        let range = 0_u8;
         let mut result = 0;
         if FSUIPC_Write(range_a_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
 
-        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
+        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &no_limit as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
@@ -257,22 +377,24 @@ fn set_preferences() -> Result<(), Error> {
 }
 
 
-pub fn get_aircraft(on_ground: bool) -> Result<Vec<TcasData>, Error> {
+/// Reads one of the TCAS tables, limited to `range_nm` around our aircraft or unlimited if 0.
+pub fn get_aircraft(on_ground: bool, range_nm: u8) -> Result<Vec<TcasData>, Error> {
     unsafe {
-        set_preferences()?;
+        set_preferences(range_nm)?;
         let (range_a_offset, range_b_offset, tcas_id_option_offset) = if on_ground {
             (0xE068, 0xE069, 0xE06A)
         } else {
             (0xF068, 0xF069, 0xF06A)
         };
-        let range = 0_u8;
+        let range = range_nm;
+        let no_limit = 0_u8;
         let mut result = 0;
         if FSUIPC_Write(range_a_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
 
-        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &range as *const u8 as *const _, &mut result) != 1 {
+        if FSUIPC_Write(range_b_offset, mem::size_of::<u8>() as u32, &no_limit as *const u8 as *const _, &mut result) != 1 {
             let result: Error = mem::transmute(result);
             return Err(result);
         }
@@ -323,15 +445,14 @@ pub fn get_aircraft(on_ground: bool) -> Result<Vec<TcasData>, Error> {
         }
 
 
-
-        return Ok(ret_vec);
+        Ok(ret_vec)
     }
     
 
 }
 
 const KNOTS_PER_M_PER_S: f64 = 1.943844;
-const FEET_PER_M: f64 = 3.28084;
+const ANGLE_FACTOR: f64 = 360.0 / (65536.0 * 65536.0);
 
 pub fn get_own_aircraft_data() -> Result<OwnAircraftData, Error> {
     let mut lat: f64 = 0.0;
@@ -341,6 +462,14 @@ pub fn get_own_aircraft_data() -> Result<OwnAircraftData, Error> {
     let mut xpdr: u16 = 0;
     let mut pressure_qnh: u16 = 0;
     let mut gs_m_per_s: f64 = 0.0;
+    let mut vs_raw: i32 = 0;
+    let mut ground_alt_raw: i32 = 0;
+    let mut on_ground: u16 = 0;
+    let mut pitch_raw: i32 = 0;
+    let mut bank_raw: i32 = 0;
+    let mut xpdr_state: u8 = 0;
+    let mut xpdr_ident: u8 = 0;
+    let mut com1: u16 = 0;
     let mut res = 0;
 
     unsafe {
@@ -372,12 +501,45 @@ pub fn get_own_aircraft_data() -> Result<OwnAircraftData, Error> {
             let result: Error = mem::transmute(res);
             return Err(result);
         }
+        if FSUIPC_Read(0x02C8, mem::size_of::<i32>() as u32, &mut vs_raw as *mut i32 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x0020, mem::size_of::<i32>() as u32, &mut ground_alt_raw as *mut i32 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x0366, mem::size_of::<u16>() as u32, &mut on_ground as *mut u16 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x0578, mem::size_of::<i32>() as u32, &mut pitch_raw as *mut i32 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x057C, mem::size_of::<i32>() as u32, &mut bank_raw as *mut i32 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x0B46, mem::size_of::<u8>() as u32, &mut xpdr_state as *mut u8 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x7B93, mem::size_of::<u8>() as u32, &mut xpdr_ident as *mut u8 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
+        if FSUIPC_Read(0x034E, mem::size_of::<u16>() as u32, &mut com1 as *mut u16 as *mut _, &mut res) != 1 {
+            let result: Error = mem::transmute(res);
+            return Err(result);
+        }
         if FSUIPC_Process(&mut res) != 1 {
             let result: Error = mem::transmute(res);
             return Err(result);
         }
-        let press_alt_ft = press_alt_m * FEET_PER_M;
-        let xpdr_str = format!("{:4X}", xpdr);
+        let press_alt_ft = altimetry::metres_to_feet(press_alt_m);
+        // The squawk is BCD, so its hex digits are the code's octal digits
+        let squawk = format!("{:04X}", xpdr).parse().unwrap_or(2000);
 
 
         Ok(
@@ -387,13 +549,66 @@ pub fn get_own_aircraft_data() -> Result<OwnAircraftData, Error> {
                 pressure_alt: press_alt_ft,
                 true_hdg: true_hdg_radians.to_degrees(),
                 gs: gs_m_per_s * KNOTS_PER_M_PER_S,
-                local_qnh_in_hg: (pressure_qnh as f64 / 16.0) * 0.02952998057,
-                xpdr_str,
+                vs: altimetry::metres_to_feet(vs_raw as f64 / 256.0) * 60.0,
+                ground_alt: altimetry::metres_to_feet(ground_alt_raw as f64 / 256.0),
+                // The sim has nose down and left wing down as positive
+                pitch: -(pitch_raw as f64 * ANGLE_FACTOR),
+                bank: -(bank_raw as f64 * ANGLE_FACTOR),
+                on_ground: on_ground != 0,
+                altimeter_setting_hpa: pressure_qnh as f64 / 16.0,
+                squawk,
+                transponder_state: TransponderState::from(xpdr_state),
+                ident: xpdr_ident != 0,
+                com1: Frequency::from_bcd(com1),
             }
         )
     }
 }
 
+/// Sets our transponder code, given as its four octal digits written in decimal.
+pub fn set_squawk(code: u16) -> Result<(), Error> {
+    // BCD, as read from the same offset
+    let xpdr = u16::from_str_radix(&format!("{:04}", code), 16).map_err(|_| Error::BadData)?;
+    unsafe {
+        let mut result = 0;
+        if FSUIPC_Write(0x354, mem::size_of::<u16>() as u32, &xpdr as *const u16 as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+        if FSUIPC_Process(&mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+    }
+    Ok(())
+}
+
+/// Shows a line of text in the sim for `seconds`, or until replaced if 0.
+pub fn show_message(text: &str, seconds: i16) -> Result<(), Error> {
+    // The text buffer is 128 bytes including the terminating null
+    let mut buffer = [0_u8; 128];
+    for (dest, byte) in buffer.iter_mut().zip(text.bytes().take(127)) {
+        *dest = byte;
+    }
+    unsafe {
+        let mut result = 0;
+        if FSUIPC_Write(0x3380, buffer.len() as u32, buffer.as_ptr() as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+        // Writing the display time is what makes the sim show the text
+        if FSUIPC_Write(0x32FA, mem::size_of::<i16>() as u32, &seconds as *const i16 as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+        if FSUIPC_Process(&mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct OwnAircraftData {
     pub lat: f64,
@@ -401,105 +616,109 @@ pub struct OwnAircraftData {
     pub pressure_alt: f64,
     pub true_hdg: f64,
     pub gs: f64,
-    pub local_qnh_in_hg: f64,
-    pub xpdr_str: String,
+    pub vs: f64,
+    pub ground_alt: f64,
+    /// Degrees, nose up positive.
+    pub pitch: f64,
+    /// Degrees, right wing down positive.
+    pub bank: f64,
+    pub on_ground: bool,
+    pub altimeter_setting_hpa: f64,
+    pub squawk: u16,
+    pub transponder_state: TransponderState,
+    pub ident: bool,
+    pub com1: Option<Frequency>,
 }
 
-#[allow(unused)]
-unsafe fn get_string(ai_ac_id: u32, desired_value: StringType) -> Result<String, Error> {
-    let mut result = 0;
-    let source_a = desired_value as u32;
-    if FSUIPC_Write(0xD004, mem::size_of::<u32>() as u32, &source_a as *const u32 as *const _, &mut result) != 1 {
-        let result: Error = mem::transmute(result);
-        return Err(result);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransponderState {
+    Off,
+    Standby,
+    Test,
+    On,
+    Altitude,
+    Ground,
+}
+impl TransponderState {
+    /// Whether the transponder is replying with altitude, as far as a radar would see.
+    pub fn is_replying(&self) -> bool {
+        matches!(self, TransponderState::On | TransponderState::Altitude | TransponderState::Ground)
     }
-
-    let mut timestamp = 0_u32;
-    let mut result = 0;
-    if FSUIPC_Read(0xD008, mem::size_of::<u32>() as u32, &mut timestamp as *mut u32 as *mut _, &mut result) != 1 {
-        let result: Error = mem::transmute(result);
-        return Err(result);
+}
+impl From<u8> for TransponderState {
+    fn from(value: u8) -> TransponderState {
+        match value {
+            0 => TransponderState::Off,
+            1 => TransponderState::Standby,
+            2 => TransponderState::Test,
+            3 => TransponderState::On,
+            5 => TransponderState::Ground,
+            _ => TransponderState::Altitude,
+        }
     }
+}
 
-    let mut result = 0;
-    let source_b = ai_ac_id;
-    if FSUIPC_Write(0xD00C, mem::size_of::<u32>() as u32, &source_b as *const u32 as *const _, &mut result) != 1 {
-        let result: Error = mem::transmute(result);
-        return Err(result);
-    }
+/// Asks FSUIPC for one of an AI aircraft's strings without waiting for it. Returns the request
+/// timestamp at the time of asking; the answer is ready once it moves on.
+pub fn request_string(ai_ac_id: u32, desired_value: StringType) -> Result<u32, Error> {
+    unsafe {
+        let mut result = 0;
+        let source_a = desired_value as u32;
+        if FSUIPC_Write(0xD004, mem::size_of::<u32>() as u32, &source_a as *const u32 as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
 
-    let mut result = 0;
-    let source_c = 16_u32;
-    if FSUIPC_Write(0xD000, mem::size_of::<u32>() as u32, &source_c as *const u32 as *const _, &mut result) != 1 {
-        let result: Error = mem::transmute(result);
-        return Err(result);
-    }
+        let mut timestamp = 0_u32;
+        let mut result = 0;
+        if FSUIPC_Read(0xD008, mem::size_of::<u32>() as u32, &mut timestamp as *mut u32 as *mut _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
 
-    let mut result = 0;
-    if FSUIPC_Process(&mut result) != 1 {
-        let result: Error = mem::transmute(result);
-        return Err(result);
-    }
+        let mut result = 0;
+        let source_b = ai_ac_id;
+        if FSUIPC_Write(0xD00C, mem::size_of::<u32>() as u32, &source_b as *const u32 as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
+        }
 
-    let mut i = 0;
-    loop {
-        if i == 200 {
-            return Err(Error::TimedOut);
+        let mut result = 0;
+        let source_c = 16_u32;
+        if FSUIPC_Write(0xD000, mem::size_of::<u32>() as u32, &source_c as *const u32 as *const _, &mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
         }
-        let new_timestamp: u32 = read(0xD008)?;
-        if new_timestamp == timestamp {
-            thread::sleep(std::time::Duration::from_millis(10));
-            i += 1;
-            continue;
+
+        let mut result = 0;
+        if FSUIPC_Process(&mut result) != 1 {
+            let result: Error = mem::transmute(result);
+            return Err(result);
         }
+        Ok(timestamp)
+    }
+}
 
-        let string: [u8; 48] = read(0xD010)?;
-        let c_str = CStr::from_bytes_until_nul(&string).unwrap();
-        let str = c_str.to_str().unwrap();
-        return Ok(str.to_owned());
+/// The answer to the last `request_string`, if it has arrived.
+pub fn poll_string(request_timestamp: u32) -> Result<Option<String>, Error> {
+    let new_timestamp: u32 = read(0xD008)?;
+    if new_timestamp == request_timestamp {
+        return Ok(None);
     }
+
+    let string: [u8; 48] = read(0xD010)?;
+    let c_str = CStr::from_bytes_until_nul(&string).map_err(|_| Error::BadData)?;
+    Ok(Some(c_str.to_string_lossy().into_owned()))
 }
 
 
 #[allow(unused)]
 #[derive(Debug, Clone, Copy)]
 #[repr(u32)]
-enum StringType {
+pub enum StringType {
     TailNumber = 1,
     AirlineNameAndFlightNumber,
     AtcAcTypeAndModel,
     AircraftTitle,
     AtcAcTypeAndLastThreeOfTail,
 }
-
-#[allow(unused)]
-#[derive(Debug)]
-struct StringData {
-    tail_number: String,
-    airline_name_and_flight_number: String,
-    atc_ac_type_and_model: String,
-    aircraft_title: String,
-    atc_ac_type_and_last_three_of_tail: String,
-}
-impl StringData {
-    #[allow(unused)]
-    pub fn get(ai_ac_id: u32) -> Result<StringData, Error> {
-        unsafe {
-            let tail_number = get_string(ai_ac_id, StringType::TailNumber)?;
-            let airline_name_and_flight_number = get_string(ai_ac_id, StringType::AirlineNameAndFlightNumber)?;
-            let atc_ac_type_and_model = get_string(ai_ac_id, StringType::AtcAcTypeAndModel)?;
-            let aircraft_title = get_string(ai_ac_id, StringType::AircraftTitle)?;
-            let atc_ac_type_and_last_three_of_tail = get_string(ai_ac_id, StringType::AtcAcTypeAndLastThreeOfTail)?;
-
-            Ok(
-                StringData {
-                    tail_number,
-                    airline_name_and_flight_number,
-                    atc_ac_type_and_model,
-                    aircraft_title,
-                    atc_ac_type_and_last_three_of_tail
-                }
-            )
-        }
-    }
-}
\ No newline at end of file
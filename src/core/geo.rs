@@ -0,0 +1,38 @@
+pub const EARTH_RADIUS_NM: f64 = 3440.065;
+pub const NM_PER_DEGREE_LAT: f64 = 60.0;
+const WGS84_SEMI_MAJOR_AXIS_M: f64 = 6_378_137.0;
+const WGS84_FLATTENING: f64 = 1.0 / 298.257223563;
+
+/// Great-circle distance between two points, in nautical miles.
+pub fn distance_nm(lat_a: f64, lon_a: f64, lat_b: f64, lon_b: f64) -> f64 {
+    let (lat_a, lat_b) = (lat_a.to_radians(), lat_b.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (lon_b - lon_a).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_NM * a.sqrt().min(1.0).asin()
+}
+
+/// The point reached by travelling `distance_nm` from a start point along an initial true bearing.
+pub fn destination(lat: f64, lon: f64, bearing_deg: f64, distance_nm: f64) -> (f64, f64) {
+    let (lat, lon, bearing) = (lat.to_radians(), lon.to_radians(), bearing_deg.to_radians());
+    let angular_distance = distance_nm / EARTH_RADIUS_NM;
+    let dest_lat = (lat.sin() * angular_distance.cos() + lat.cos() * angular_distance.sin() * bearing.cos()).asin();
+    let dest_lon = lon + (bearing.sin() * angular_distance.sin() * lat.cos()).atan2(angular_distance.cos() - lat.sin() * dest_lat.sin());
+    (dest_lat.to_degrees(), (dest_lon.to_degrees() + 540.0).rem_euclid(360.0) - 180.0)
+}
+
+/// Latitude and longitude in degrees and height above the WGS84 ellipsoid in metres, from
+/// earth-centred, earth-fixed coordinates in metres. Not meant for use near the poles.
+pub fn ecef_to_geodetic(x: f64, y: f64, z: f64) -> (f64, f64, f64) {
+    let e2 = WGS84_FLATTENING * (2.0 - WGS84_FLATTENING);
+    let p = x.hypot(y);
+    let mut lat = z.atan2(p * (1.0 - e2));
+    let mut height = 0.0;
+    // Converges to well under a millimetre in a few rounds
+    for _ in 0..5 {
+        let n = WGS84_SEMI_MAJOR_AXIS_M / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        height = p / lat.cos() - n;
+        lat = z.atan2(p * (1.0 - e2 * n / (n + height)));
+    }
+    (lat.to_degrees(), y.atan2(x).to_degrees(), height)
+}
@@ -1,6 +1,6 @@
-use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::Duration};
+use std::{collections::{BTreeMap, HashMap}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::Duration};
 
-use crate::ui::Message;
+use super::{altimetry, util::glob_match};
 
 
 const VATSIM_METARS_URL: &str = "https://metar.vatsim.net/metar.php?id=all";
@@ -10,6 +10,7 @@ const VATSIM_METARS_URL: &str = "https://metar.vatsim.net/metar.php?id=all";
 #[derive(Clone)]
 pub struct MetarProvider {
     metars: Arc<Mutex<HashMap<String, String>>>,
+    qnhs: Arc<Mutex<HashMap<String, f64>>>,
     last_update_successful: Arc<AtomicBool>,
 }
 impl MetarProvider {
@@ -17,6 +18,7 @@ impl MetarProvider {
     pub fn new() -> MetarProvider {
         MetarProvider {
             metars: Arc::new(Mutex::new(HashMap::new())),
+            qnhs: Arc::new(Mutex::new(HashMap::new())),
             last_update_successful: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -32,7 +34,38 @@ impl MetarProvider {
     }
 
     pub fn lookup_metar(&self, station_id: &str) -> Option<String> {
-        self.metars.lock().unwrap().get(station_id).clone().map(|x| x.to_owned())
+        self.metars.lock().unwrap().get(station_id).cloned()
+    }
+
+    /// The decoded QNH in hPa from a station's latest METAR.
+    pub fn lookup_qnh(&self, station_id: &str) -> Option<f64> {
+        self.qnhs.lock().unwrap().get(station_id).copied()
+    }
+
+    #[cfg(test)]
+    pub fn insert_metar(&self, station_id: &str, metar: &str) {
+        self.metars.lock().unwrap().insert(station_id.to_owned(), metar.to_owned());
+    }
+
+    /// Looks up METARs for a query of one or more stations separated by spaces or commas.
+    ///
+    /// Each term is either a full ICAO code, a prefix such as `EGL`, or a wildcard pattern
+    /// such as `K*` or `EG??`. The results are sorted by station and contain no duplicates.
+    #[cfg_attr(not(windows), allow(unused))]
+    pub fn search_metars(&self, query: &str) -> Vec<String> {
+        let metars = self.metars.lock().unwrap();
+        let mut results = BTreeMap::new();
+        for term in query.split(|c: char| c.is_whitespace() || c == ',' || c == ';').filter(|term| !term.is_empty()) {
+            let term = term.to_uppercase();
+            if term.contains(['*', '?']) {
+                results.extend(metars.iter().filter(|(icao, _)| glob_match(&term, icao)));
+            } else if term.len() < 4 {
+                results.extend(metars.iter().filter(|(icao, _)| icao.starts_with(&term)));
+            } else if let Some(metar) = metars.get_key_value(&term) {
+                results.insert(metar.0, metar.1);
+            }
+        }
+        results.into_values().cloned().collect()
     }
 
     fn update_inner(&mut self) -> bool {
@@ -51,8 +84,152 @@ impl MetarProvider {
             None => return false,
         }
 
+        let qnhs = map.iter().filter_map(|(icao, metar)| DecodedMetar::parse(metar).and_then(|decoded| decoded.qnh_hpa).map(|qnh| (icao.clone(), qnh))).collect();
+        *self.qnhs.lock().unwrap() = qnhs;
+
         let mut lock = self.metars.lock().unwrap();
         *lock = map;
-        return true;
+        true
     }
-}
\ No newline at end of file
+}
+
+
+
+#[derive(Debug, Clone, Default)]
+pub struct DecodedMetar {
+    pub station: String,
+    /// Observation time as (day, hour, minute) UTC.
+    pub time: Option<(u8, u8, u8)>,
+    pub wind: Option<Wind>,
+    pub qnh_hpa: Option<f64>,
+    pub temperature: Option<i32>,
+    pub dewpoint: Option<i32>,
+    /// The weather groups between the wind and the QNH, as reported.
+    pub weather: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Wind {
+    /// `None` if the wind is variable.
+    pub direction: Option<u16>,
+    pub speed_kt: u16,
+    pub gust_kt: Option<u16>,
+}
+
+impl DecodedMetar {
+    pub fn parse(raw: &str) -> Option<DecodedMetar> {
+        let mut tokens = raw.split_whitespace().peekable();
+        if matches!(tokens.peek(), Some(&"METAR") | Some(&"SPECI")) {
+            tokens.next();
+        }
+        let station = tokens.next().filter(|station| station.len() == 4)?.to_owned();
+        let mut decoded = DecodedMetar { station, ..Default::default() };
+
+        for token in tokens {
+            if matches!(token, "RMK" | "TEMPO" | "BECMG" | "NOSIG") { break; }
+            if matches!(token, "AUTO" | "COR" | "=") { continue; }
+
+            // Nothing below makes sense of anything else, and slicing it could split a character
+            if !token.is_ascii() {
+                decoded.weather.push(token.to_owned());
+                continue;
+            }
+            if decoded.time.is_none() && token.len() == 7 && token.ends_with('Z') {
+                if let (Ok(day), Ok(hour), Ok(min)) = (token[0..2].parse(), token[2..4].parse(), token[4..6].parse()) {
+                    decoded.time = Some((day, hour, min));
+                    continue;
+                }
+            }
+            if decoded.wind.is_none() {
+                if let Some(wind) = Wind::parse(token) {
+                    decoded.wind = Some(wind);
+                    continue;
+                }
+            }
+            if let Some(qnh) = parse_qnh(token) {
+                decoded.qnh_hpa = Some(qnh);
+                continue;
+            }
+            if let Some((temp, dew)) = parse_temperatures(token) {
+                decoded.temperature = Some(temp);
+                decoded.dewpoint = dew;
+                continue;
+            }
+            decoded.weather.push(token.to_owned());
+        }
+        Some(decoded)
+    }
+}
+
+impl Wind {
+    fn parse(token: &str) -> Option<Wind> {
+        let (body, mps) = if let Some(body) = token.strip_suffix("KT") {
+            (body, false)
+        } else if let Some(body) = token.strip_suffix("MPS") {
+            (body, true)
+        } else {
+            return None;
+        };
+        if body.len() < 5 || !body.is_ascii() { return None; }
+        let direction = match &body[0..3] {
+            "VRB" => None,
+            dir => Some(dir.parse::<u16>().ok()?),
+        };
+        let (speed, gust) = match body[3..].split_once('G') {
+            Some((speed, gust)) => (speed.parse::<u16>().ok()?, Some(gust.parse::<u16>().ok()?)),
+            None => (body[3..].parse::<u16>().ok()?, None),
+        };
+        let to_kt = |value: u16| if mps { (value as f64 * 1.943844).round() as u16 } else { value };
+        Some(Wind { direction, speed_kt: to_kt(speed), gust_kt: gust.map(to_kt) })
+    }
+}
+
+fn parse_qnh(token: &str) -> Option<f64> {
+    if token.len() != 5 || !token.is_ascii() || !token[1..].chars().all(|c| c.is_ascii_digit()) { return None; }
+    let value = token[1..].parse::<f64>().ok()?;
+    match &token[0..1] {
+        "Q" => Some(value),
+        "A" => Some(altimetry::in_hg_to_hpa(value / 100.0)),
+        _ => None,
+    }
+}
+
+fn parse_temperatures(token: &str) -> Option<(i32, Option<i32>)> {
+    let (temp, dew) = token.split_once('/')?;
+    let parse = |value: &str| {
+        let (negative, digits) = match value.strip_prefix('M') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+        if digits.len() != 2 { return None; }
+        digits.parse::<i32>().ok().map(|v| if negative { -v } else { v })
+    };
+    Some((parse(temp)?, parse(dew)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_a_metar() {
+        let metar = DecodedMetar::parse("EGLL 191150Z 24015G25KT 9999 -RA BKN012 14/11 Q1008 NOSIG").unwrap();
+        assert_eq!(metar.station, "EGLL");
+        assert_eq!(metar.time, Some((19, 11, 50)));
+        let wind = metar.wind.unwrap();
+        assert_eq!((wind.direction, wind.speed_kt, wind.gust_kt), (Some(240), 15, Some(25)));
+        assert_eq!(metar.weather, ["9999", "-RA", "BKN012"]);
+        assert_eq!((metar.temperature, metar.dewpoint), (Some(14), Some(11)));
+        assert_eq!(metar.qnh_hpa, Some(1008.0));
+    }
+
+    #[test]
+    fn does_not_panic_on_multibyte_groups() {
+        let metar = DecodedMetar::parse("EGLL 1€1150Z €2015KT 2€015KT Q€10 €1008 14/11").unwrap();
+        assert!(metar.time.is_none());
+        assert!(metar.wind.is_none());
+        assert!(metar.qnh_hpa.is_none());
+        assert_eq!(metar.temperature, Some(14));
+    }
+}
@@ -1,39 +1,102 @@
-use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, mpsc::Sender, Arc, Mutex}, thread::JoinHandle};
+use std::{collections::HashMap, fs, sync::{atomic::{AtomicBool, AtomicU32, AtomicU8, AtomicUsize, Ordering}, mpsc, Arc, Mutex}, thread::JoinHandle, time::SystemTime};
 
 use crate::ui::{Message, Ui};
 
-use self::{fsd::Server, metar::MetarProvider, vatsim::VatsimDataProvider};
+use self::{airports::{AirportDatabase, DEFAULT_AIRPORTS_FILE}, altimetry::Transition, callsign_map::CallsignMap, config::Config, correlation::CorrelationSettings, enrichment::AircraftInfoProvider, filter::{AreaFilter, CallsignFilter, LevelFilter}, frequency::{Frequency, FrequencyProvider}, squawk::SquawkSettings, vatsim::RatingLevel, fsd::Server, metar::MetarProvider, sim_flight_plan::SimFlightPlanProvider, source::SourceKind, vatsim::VatsimDataProvider};
 
-mod worker;
 mod fsd;
 mod metar;
 mod vatsim;
 mod fsuipc;
+mod atis;
+mod config;
+mod util;
+mod geo;
+mod airports;
+mod altimetry;
+mod track;
+mod fast_position;
+mod dead_reckoning;
+mod squawk;
+mod sim_flight_plan;
+mod enrichment;
+mod frequency;
+mod filter;
+mod callsign_map;
+mod correlation;
+mod source;
+
+const CONFIG_FILE: &str = "traffic-viewer.ini";
+const DEFAULT_FAST_POSITION_INTERVAL: usize = 1;
+
 pub struct App<U: Ui> {
     thread: Option<JoinHandle<()>>,
+    /// Only held so the server is shut down along with the app.
+    #[allow(unused)]
     fsd: Server,
+    #[cfg_attr(not(windows), allow(unused))]
     metar_provider: MetarProvider,
-    vatsim_data_provider: VatsimDataProvider,
+    airports: AirportDatabase,
     pub preferences: Preferences,
     should_terminate: Arc<AtomicBool>,
+    /// When the settings file had last been saved as of reading it.
+    config_modified: Option<SystemTime>,
     ui_link: U
 }
 impl<U> App<U> where U: Ui + 'static {
-    pub fn new(preferences: Preferences, ui_link: U) -> Self {
-        let metar_provider = MetarProvider::new();
-        let vatsim_data_provider = VatsimDataProvider::new();
+    pub fn new(mut preferences: Preferences, ui_link: U) -> Self {
+        let config_modified = config_modified();
+        let config = Config::load(CONFIG_FILE);
+        preferences.load_config(&config);
+        let airports = AirportDatabase::new();
+        airports.load_in_background(config.get("airports", "file").unwrap_or(DEFAULT_AIRPORTS_FILE), ui_link.clone());
+        let shared = Shared {
+            preferences: preferences.clone(),
+            metar_provider: MetarProvider::new(),
+            vatsim_data_provider: VatsimDataProvider::new(),
+            airports: airports.clone(),
+            sim_flight_plan_provider: SimFlightPlanProvider::new(),
+            aircraft_info_provider: AircraftInfoProvider::new(),
+            frequency_provider: FrequencyProvider::new(),
+        };
+        let metar_provider = shared.metar_provider.clone();
         let should_terminate = Arc::new(AtomicBool::new(false));
-        let fsd = Server::new(preferences.clone(), vatsim_data_provider.clone(), metar_provider.clone(), ui_link.clone(), Arc::clone(&should_terminate));
-        let thread = Some(worker::worker_thread(Arc::clone(&should_terminate), preferences.clone(), ui_link.clone(), metar_provider.clone(), vatsim_data_provider.clone(), fsd.sender()));
-        Self { thread, fsd, metar_provider, vatsim_data_provider, preferences, should_terminate, ui_link }
-    }
-    pub fn try_lookup_metar(&self, station_id: String) {
-        let message = match self.metar_provider.lookup_metar(&station_id) {
-            Some(metar) => Message::MetarRetrieved(metar),
-            None => Message::MetarNotFound,
+        let (sim_command_sender, sim_command_receiver) = mpsc::channel();
+        let fsd = Server::new(shared.clone(), sim_command_sender, ui_link.clone(), Arc::clone(&should_terminate));
+        let source = SourceKind::from_config(&config).open(preferences.clone(), shared.vatsim_data_provider.clone(), airports.clone());
+        let thread = Some(source::source_thread(Arc::clone(&should_terminate), shared, ui_link.clone(), source, sim_command_receiver, fsd.sender()));
+        Self { thread, fsd, metar_provider, airports, preferences, should_terminate, config_modified, ui_link }
+    }
+    #[cfg_attr(not(windows), allow(unused))]
+    pub fn try_search_metars(&self, query: String) {
+        let mut metars = self.metar_provider.search_metars(&query);
+        let message = match metars.len() {
+            0 => Message::MetarNotFound,
+            1 => Message::MetarRetrieved(metars.remove(0)),
+            _ => Message::MetarsRetrievedMultiple(metars),
         };
         self.ui_link.dispatch_message(message);
     }
+    /// Re-reads the settings file, so filters and the like can be changed without restarting,
+    /// and the airport database with it. Only the `[source]` section needs a restart.
+    pub fn reload_config(&mut self) {
+        self.config_modified = config_modified();
+        let config = Config::load(CONFIG_FILE);
+        self.preferences.load_config(&config);
+        self.airports.load_in_background(config.get("airports", "file").unwrap_or(DEFAULT_AIRPORTS_FILE), self.ui_link.clone());
+        self.ui_link.dispatch_message(Message::ConfigReloaded);
+    }
+    /// Reloads the settings file if it has been saved since it was last read, for UIs
+    /// without a button to do it.
+    #[cfg_attr(windows, allow(unused))]
+    pub fn reload_config_if_changed(&mut self) {
+        if config_modified() != self.config_modified {
+            self.reload_config();
+        }
+    }
+}
+fn config_modified() -> Option<SystemTime> {
+    fs::metadata(CONFIG_FILE).and_then(|metadata| metadata.modified()).ok()
 }
 impl<U> Drop for App<U> where U: Ui {
     fn drop(&mut self) {
@@ -47,6 +110,19 @@ impl<U> Drop for App<U> where U: Ui {
 
 
 
+/// Handles to what the app's threads share, so each can be handed the lot in one go. Clones
+/// share the same data.
+#[derive(Clone)]
+pub struct Shared {
+    pub preferences: Preferences,
+    pub metar_provider: MetarProvider,
+    pub vatsim_data_provider: VatsimDataProvider,
+    pub airports: AirportDatabase,
+    pub sim_flight_plan_provider: SimFlightPlanProvider,
+    pub aircraft_info_provider: AircraftInfoProvider,
+    pub frequency_provider: FrequencyProvider,
+}
+
 #[derive(Clone)]
 pub struct Preferences {
     own_callsign: Arc<Mutex<String>>,
@@ -55,6 +131,19 @@ pub struct Preferences {
     fetch_metars: Arc<AtomicBool>,
     fetch_flight_plans: Arc<AtomicBool>,
     only_show_vatsim: Arc<AtomicBool>,
+    atis_runways: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    transition_altitude: Arc<AtomicU32>,
+    fast_position_interval: Arc<AtomicUsize>,
+    extrapolate_positions: Arc<AtomicBool>,
+    squawk_settings: Arc<Mutex<SquawkSettings>>,
+    default_pilot_rating: Arc<AtomicU8>,
+    write_assigned_squawk: Arc<AtomicBool>,
+    frequency_filter: Arc<AtomicU32>,
+    area_filter: Arc<Mutex<AreaFilter>>,
+    level_filter: Arc<Mutex<LevelFilter>>,
+    callsign_filter: Arc<Mutex<CallsignFilter>>,
+    callsign_map: Arc<Mutex<CallsignMap>>,
+    correlation_settings: Arc<Mutex<CorrelationSettings>>,
 }
 impl Preferences {
     pub fn new(use_es_callsign: bool, fetch_metars: bool, fetch_flight_plans: bool, only_show_vatsim: bool) -> Preferences {
@@ -65,27 +154,67 @@ impl Preferences {
             fetch_metars: Arc::new(AtomicBool::new(fetch_metars)),
             fetch_flight_plans: Arc::new(AtomicBool::new(fetch_flight_plans)),
             only_show_vatsim: Arc::new(AtomicBool::new(only_show_vatsim)),
+            atis_runways: Arc::new(Mutex::new(HashMap::new())),
+            transition_altitude: Arc::new(AtomicU32::new(Transition::default().altitude_ft as u32)),
+            fast_position_interval: Arc::new(AtomicUsize::new(DEFAULT_FAST_POSITION_INTERVAL)),
+            extrapolate_positions: Arc::new(AtomicBool::new(false)),
+            squawk_settings: Arc::new(Mutex::new(SquawkSettings::default())),
+            default_pilot_rating: Arc::new(AtomicU8::new(RatingLevel::Student as u8)),
+            write_assigned_squawk: Arc::new(AtomicBool::new(false)),
+            frequency_filter: Arc::new(AtomicU32::new(0)),
+            area_filter: Arc::new(Mutex::new(AreaFilter::default())),
+            level_filter: Arc::new(Mutex::new(LevelFilter::default())),
+            callsign_filter: Arc::new(Mutex::new(CallsignFilter::default())),
+            callsign_map: Arc::new(Mutex::new(CallsignMap::default())),
+            correlation_settings: Arc::new(Mutex::new(CorrelationSettings::default())),
         }
     }
+    pub fn load_config(&mut self, config: &Config) {
+        let atis_runways = config.section("runways").iter().map(|(icao, runways)| {
+            (icao.to_uppercase(), runways.split_whitespace().map(|rwy| rwy.to_uppercase()).collect())
+        }).collect();
+        self.set_atis_runways(atis_runways);
+        // Anything left out goes back to its default, so taking a line out and reloading undoes it
+        self.set_transition_altitude(config.get_parsed("altimetry", "transition_altitude").unwrap_or(Transition::default().altitude_ft as u32));
+        self.set_fast_position_interval(config.get_parsed("positions", "fast_interval").unwrap_or(DEFAULT_FAST_POSITION_INTERVAL));
+        self.set_extrapolate_positions(config.get_bool("positions", "extrapolate").unwrap_or(false));
+        self.set_squawk_settings(SquawkSettings::from_config(config));
+        self.set_default_pilot_rating(config.get("ratings", "default").and_then(RatingLevel::from_name).unwrap_or(RatingLevel::Student));
+        self.set_write_assigned_squawk(config.get_bool("simulator", "write_assigned_squawk").unwrap_or(false));
+        self.set_frequency_filter(config.get_parsed("filters", "frequency"));
+        self.set_area_filter(AreaFilter::from_config(config));
+        self.set_level_filter(LevelFilter::from_config(config));
+        self.set_callsign_filter(CallsignFilter::from_config(config));
+        self.set_callsign_map(CallsignMap::from_config(config));
+        self.set_correlation_settings(CorrelationSettings::from_config(config));
+    }
     pub fn own_callsign(&self) -> Option<String> {
         let own_callsign = self.own_callsign.lock().unwrap();
-        return if own_callsign.is_empty() {
+        if own_callsign.is_empty() {
             None
         } else {
             Some(own_callsign.clone())
-        };
+        }
     }
     pub fn es_callsign(&self) -> Option<String> {
         let es_callsign = self.es_callsign.lock().unwrap();
-        return if es_callsign.is_empty() {
+        if es_callsign.is_empty() {
             None
         } else {
             Some(es_callsign.clone())
-        };
+        }
     }
     pub fn use_es_callsign(&self) -> bool {
         self.use_es_callsign.load(Ordering::Relaxed)
     }
+    /// The callsign our own aircraft is sent as.
+    pub fn pilot_callsign(&self) -> String {
+        if self.use_es_callsign() {
+            self.es_callsign()
+        } else {
+            self.own_callsign()
+        }.unwrap_or_else(|| String::from("ME"))
+    }
     pub fn fetch_metars(&self) -> bool {
         self.fetch_metars.load(Ordering::Relaxed)
     }
@@ -95,7 +224,54 @@ impl Preferences {
     pub fn only_show_vatsim(&self) -> bool {
         self.only_show_vatsim.load(Ordering::Relaxed)
     }
+    /// Seconds between fast position packets, or 0 if they are not sent.
+    pub fn fast_position_interval(&self) -> usize {
+        self.fast_position_interval.load(Ordering::Relaxed)
+    }
+    pub fn extrapolate_positions(&self) -> bool {
+        self.extrapolate_positions.load(Ordering::Relaxed)
+    }
+    pub fn squawk_settings(&self) -> SquawkSettings {
+        self.squawk_settings.lock().unwrap().clone()
+    }
+    /// The rating sent for aircraft that are not on the network.
+    pub fn default_pilot_rating(&self) -> RatingLevel {
+        RatingLevel::from_u8(self.default_pilot_rating.load(Ordering::Relaxed))
+    }
+    /// Whether squawks assigned to us from EuroScope are set on the sim's transponder.
+    pub fn write_assigned_squawk(&self) -> bool {
+        self.write_assigned_squawk.load(Ordering::Relaxed)
+    }
+    /// Only traffic tuned to this frequency is shown, if set.
+    pub fn frequency_filter(&self) -> Option<Frequency> {
+        match self.frequency_filter.load(Ordering::Relaxed) {
+            0 => None,
+            khz => Some(Frequency(khz)),
+        }
+    }
+    pub fn area_filter(&self) -> AreaFilter {
+        self.area_filter.lock().unwrap().clone()
+    }
+    pub fn level_filter(&self) -> LevelFilter {
+        *self.level_filter.lock().unwrap()
+    }
+    pub fn callsign_filter(&self) -> CallsignFilter {
+        self.callsign_filter.lock().unwrap().clone()
+    }
+    pub fn callsign_map(&self) -> CallsignMap {
+        self.callsign_map.lock().unwrap().clone()
+    }
+    pub fn correlation_settings(&self) -> CorrelationSettings {
+        self.correlation_settings.lock().unwrap().clone()
+    }
+    pub fn transition(&self) -> Transition {
+        Transition::new(self.transition_altitude.load(Ordering::Relaxed) as f64)
+    }
+    pub fn atis_runways(&self, icao: &str) -> Vec<String> {
+        self.atis_runways.lock().unwrap().get(icao).cloned().unwrap_or_default()
+    }
 
+    #[cfg_attr(not(windows), allow(unused))]
     pub fn set_own_callsign(&mut self, callsign: String) {
         let mut own_callsign = self.own_callsign.lock().unwrap();
         *own_callsign = callsign;
@@ -104,16 +280,95 @@ impl Preferences {
         let mut es_callsign = self.es_callsign.lock().unwrap();
         *es_callsign = callsign;
     }
+    #[cfg_attr(not(windows), allow(unused))]
     pub fn set_use_es_callsign(&self, val: bool) {
         self.use_es_callsign.store(val, Ordering::Relaxed)
     }
+    #[cfg_attr(not(windows), allow(unused))]
     pub fn set_fetch_metars(&self, val: bool) {
         self.fetch_metars.store(val, Ordering::Relaxed)
     }
+    #[cfg_attr(not(windows), allow(unused))]
     pub fn set_fetch_flight_plans(&self, val: bool) {
         self.fetch_flight_plans.store(val, Ordering::Relaxed)
     }
+    #[cfg_attr(not(windows), allow(unused))]
     pub fn set_only_show_vatsim(&self, val: bool) {
         self.only_show_vatsim.store(val, Ordering::Relaxed)
     }
-}
\ No newline at end of file
+    pub fn set_fast_position_interval(&self, secs: usize) {
+        self.fast_position_interval.store(secs, Ordering::Relaxed)
+    }
+    pub fn set_extrapolate_positions(&self, val: bool) {
+        self.extrapolate_positions.store(val, Ordering::Relaxed)
+    }
+    pub fn set_squawk_settings(&mut self, settings: SquawkSettings) {
+        let mut squawk_settings = self.squawk_settings.lock().unwrap();
+        *squawk_settings = settings;
+    }
+    pub fn set_default_pilot_rating(&self, rating: RatingLevel) {
+        self.default_pilot_rating.store(rating as u8, Ordering::Relaxed)
+    }
+    pub fn set_write_assigned_squawk(&self, val: bool) {
+        self.write_assigned_squawk.store(val, Ordering::Relaxed)
+    }
+    pub fn set_frequency_filter(&self, frequency: Option<Frequency>) {
+        self.frequency_filter.store(frequency.map(|frequency| frequency.0).unwrap_or(0), Ordering::Relaxed)
+    }
+    pub fn set_area_filter(&mut self, filter: AreaFilter) {
+        let mut area_filter = self.area_filter.lock().unwrap();
+        *area_filter = filter;
+    }
+    pub fn set_level_filter(&mut self, filter: LevelFilter) {
+        let mut level_filter = self.level_filter.lock().unwrap();
+        *level_filter = filter;
+    }
+    pub fn set_callsign_filter(&mut self, filter: CallsignFilter) {
+        let mut callsign_filter = self.callsign_filter.lock().unwrap();
+        *callsign_filter = filter;
+    }
+    pub fn set_callsign_map(&mut self, map: CallsignMap) {
+        let mut callsign_map = self.callsign_map.lock().unwrap();
+        *callsign_map = map;
+    }
+    pub fn set_correlation_settings(&mut self, settings: CorrelationSettings) {
+        let mut correlation_settings = self.correlation_settings.lock().unwrap();
+        *correlation_settings = settings;
+    }
+    pub fn set_transition_altitude(&self, feet: u32) {
+        self.transition_altitude.store(feet, Ordering::Relaxed)
+    }
+    pub fn set_atis_runways(&mut self, runways: HashMap<String, Vec<String>>) {
+        let mut atis_runways = self.atis_runways.lock().unwrap();
+        *atis_runways = runways;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reloading_without_a_setting_restores_its_default() {
+        let mut preferences = Preferences::new(false, false, false, false);
+        preferences.load_config(&Config::parse("[altimetry]\ntransition_altitude = 18000\n\
+            [positions]\nfast_interval = 5\nextrapolate = true\n\
+            [ratings]\ndefault = ifr\n\
+            [simulator]\nwrite_assigned_squawk = true\n\
+            [filters]\nexclude = ASXGS*\n"));
+        assert_eq!(preferences.transition().altitude_ft, 18000.0);
+        assert_eq!(preferences.fast_position_interval(), 5);
+        assert!(preferences.extrapolate_positions());
+        assert!(matches!(preferences.default_pilot_rating(), RatingLevel::Ifr));
+        assert!(preferences.write_assigned_squawk());
+        assert!(!preferences.callsign_filter().allows("ASXGS1"));
+
+        preferences.load_config(&Config::default());
+        assert_eq!(preferences.transition().altitude_ft, Transition::default().altitude_ft);
+        assert_eq!(preferences.fast_position_interval(), DEFAULT_FAST_POSITION_INTERVAL);
+        assert!(!preferences.extrapolate_positions());
+        assert!(matches!(preferences.default_pilot_rating(), RatingLevel::Student));
+        assert!(!preferences.write_assigned_squawk());
+        assert!(preferences.callsign_filter().allows("ASXGS1"));
+    }
+}
@@ -0,0 +1,133 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use super::{airports::AirportDatabase, enrichment::AircraftInfo, fsuipc::AiRoute};
+
+/// ICAO's designator for an aircraft type that has none.
+const UNKNOWN_AIRCRAFT_TYPE: &str = "ZZZZ";
+
+
+
+/// A flight plan made up from what the sim knows about an AI aircraft that isn't on VATSIM.
+#[derive(Debug, Clone)]
+pub struct SimFlightPlan {
+    pub aircraft_type: String,
+    pub departure: Option<String>,
+    pub destination: Option<String>,
+    pub telephony: Option<String>,
+}
+
+impl SimFlightPlan {
+    pub fn new(info: &AircraftInfo, route: Option<&AiRoute>) -> SimFlightPlan {
+        SimFlightPlan {
+            aircraft_type: info.type_designator().unwrap_or_else(|| String::from(UNKNOWN_AIRCRAFT_TYPE)),
+            departure: route.and_then(|route| route.departure.clone()),
+            destination: route.and_then(|route| route.destination.clone()),
+            telephony: info.telephony(),
+        }
+    }
+
+    /// Leaves out a departure or destination that isn't a known airport, such as a sim's made-up
+    /// strip, so it isn't filed. Until the airport database has loaded, everything is kept.
+    pub fn check_airports(&mut self, airports: &AirportDatabase) {
+        if !airports.is_loaded() { return }
+        let is_known = |icao: &Option<String>| icao.as_deref().is_some_and(|icao| airports.get(icao).is_some());
+        if !is_known(&self.departure) {
+            self.departure = None;
+        }
+        if !is_known(&self.destination) {
+            self.destination = None;
+        }
+    }
+}
+
+impl From<SimFlightPlan> for fsd_interface::FlightPlan {
+    fn from(value: SimFlightPlan) -> Self {
+        // Airline AI going from A to B is flying IFR; anything else is most likely GA pottering about
+        let flight_rules = if value.departure.is_some() && value.destination.is_some() {
+            fsd_interface::FlightRules::IFR
+        } else {
+            fsd_interface::FlightRules::VFR
+        };
+        Self {
+            flight_rules,
+            ac_type: value.aircraft_type,
+            filed_tas: 0,
+            origin: value.departure.unwrap_or_default(),
+            etd: 0,
+            atd: 0,
+            cruise_level: 0,
+            destination: value.destination.unwrap_or_default(),
+            hours_enroute: 0,
+            mins_enroute: 0,
+            hours_fuel: 0,
+            mins_fuel: 0,
+            alternate: String::new(),
+            remarks: value.telephony.map(|telephony| format!("CS/{}", telephony)).unwrap_or_default(),
+            route: String::new(),
+        }
+    }
+}
+
+
+/// Sim flight plans by callsign, shared between the source thread that builds them and the FSD
+/// server that answers flight plan queries.
+#[derive(Clone, Default)]
+pub struct SimFlightPlanProvider {
+    flight_plans: Arc<Mutex<HashMap<String, SimFlightPlan>>>,
+}
+
+impl SimFlightPlanProvider {
+    pub fn new() -> SimFlightPlanProvider {
+        SimFlightPlanProvider::default()
+    }
+
+    pub fn insert(&self, callsign: &str, flight_plan: SimFlightPlan) {
+        self.flight_plans.lock().unwrap().insert(callsign.to_owned(), flight_plan);
+    }
+
+    pub fn get(&self, callsign: &str) -> Option<SimFlightPlan> {
+        self.flight_plans.lock().unwrap().get(callsign).cloned()
+    }
+
+    pub fn remove(&self, callsign: &str) {
+        self.flight_plans.lock().unwrap().remove(callsign);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::airports::Airport;
+
+    fn flight_plan(departure: &str, destination: &str) -> SimFlightPlan {
+        SimFlightPlan {
+            aircraft_type: String::from("B738"),
+            departure: Some(departure.to_owned()),
+            destination: Some(destination.to_owned()),
+            telephony: None,
+        }
+    }
+
+    #[test]
+    fn leaves_out_unknown_airports() {
+        let airports = AirportDatabase::with_airports(vec![
+            Airport { icao: String::from("EIDW"), lat: 53.4213, lon: -6.2701, elevation_ft: Some(242.0) },
+            Airport { icao: String::from("EGLL"), lat: 51.4706, lon: -0.4619, elevation_ft: Some(83.0) },
+        ]);
+        let mut known = flight_plan("EIDW", "EGLL");
+        known.check_airports(&airports);
+        assert_eq!((known.departure.as_deref(), known.destination.as_deref()), (Some("EIDW"), Some("EGLL")));
+
+        let mut unknown = flight_plan("EIDW", "ZZZZ");
+        unknown.check_airports(&airports);
+        assert_eq!((unknown.departure.as_deref(), unknown.destination.as_deref()), (Some("EIDW"), None));
+        assert!(matches!(fsd_interface::FlightPlan::from(unknown).flight_rules, fsd_interface::FlightRules::VFR));
+    }
+
+    #[test]
+    fn keeps_airports_until_the_database_has_loaded() {
+        let mut flight_plan = flight_plan("EIDW", "ZZZZ");
+        flight_plan.check_airports(&AirportDatabase::new());
+        assert_eq!(flight_plan.destination.as_deref(), Some("ZZZZ"));
+    }
+}
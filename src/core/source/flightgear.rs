@@ -0,0 +1,365 @@
+use std::{collections::HashMap, io, net::UdpSocket, path::Path, time::{Duration, Instant}};
+
+use fsd_interface::TransponderMode;
+
+use super::{on_ground_near_field, SourceAircraft, SourceAltitude, SourceError, TrafficSource};
+use crate::core::{airports::AirportDatabase, altimetry, enrichment::AircraftInfo, geo};
+
+/// Where FlightGear is told to send to with `--multiplay=out,10,127.0.0.1,5000`.
+pub const DEFAULT_FLIGHTGEAR_PORT: u16 = 5000;
+const MAGIC: u32 = 0x4647_4653;
+/// Major version 1 of the protocol. Minor versions only add properties after the position.
+const PROTOCOL_MAJOR_VERSION: u32 = 1;
+const POSITION_MESSAGE_ID: u32 = 7;
+const HEADER_LEN: usize = 32;
+const CALLSIGN_LEN: usize = 8;
+const MODEL_LEN: usize = 96;
+/// The fixed part of a position message, before any properties.
+const POSITION_LEN: usize = 200;
+/// Aircraft not heard from for this long have left the session.
+const AIRCRAFT_TIMEOUT: Duration = Duration::from_secs(10);
+const KNOTS_PER_METRE_PER_SECOND: f64 = 1.94384;
+
+
+
+/// FlightGear sessions, from the multiplayer protocol's position messages sent over UDP.
+///
+/// Each message is an XDR (big-endian) header with the callsign, then the model path, the
+/// position as earth-centred cartesian coordinates, the orientation as an angle-axis rotation
+/// in the same frame, and velocities in the aircraft's body frame.
+pub struct FlightGearSource {
+    port: u16,
+    socket: Option<UdpSocket>,
+    aircraft: HashMap<String, FlightGearAircraft>,
+    /// Callsigns don't fit in an id, so each new one is given the next number.
+    next_id: u32,
+    /// Position messages don't say whether an aircraft is on the ground, so it is worked out from the field elevation.
+    airports: AirportDatabase,
+}
+
+#[derive(Debug, Clone)]
+struct FlightGearAircraft {
+    id: u32,
+    model: String,
+    lat: f64,
+    lon: f64,
+    alt: f64,
+    hdg: f64,
+    pitch: f64,
+    bank: f64,
+    gs: f64,
+    vs: f64,
+    time: Instant,
+}
+
+impl FlightGearSource {
+    pub fn new(port: u16, airports: AirportDatabase) -> FlightGearSource {
+        FlightGearSource { port, socket: None, aircraft: HashMap::new(), next_id: 0, airports }
+    }
+
+    fn socket(&mut self) -> io::Result<&UdpSocket> {
+        if self.socket.is_none() {
+            let socket = UdpSocket::bind(("0.0.0.0", self.port))?;
+            socket.set_nonblocking(true)?;
+            self.socket = Some(socket);
+        }
+        Ok(self.socket.as_ref().unwrap())
+    }
+
+    fn receive_available(&mut self) -> io::Result<()> {
+        let mut buf = [0; 2048];
+        loop {
+            let len = match self.socket()?.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some((callsign, position)) = decode_position_message(&buf[..len]) {
+                let id = match self.aircraft.get(&callsign) {
+                    Some(existing) => existing.id,
+                    None => {
+                        self.next_id += 1;
+                        self.next_id
+                    },
+                };
+                self.aircraft.insert(callsign, FlightGearAircraft { id, ..position });
+            }
+        }
+    }
+}
+
+impl TrafficSource for FlightGearSource {
+    fn name(&self) -> String {
+        format!("FlightGear on port {}", self.port)
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        self.receive_available()?;
+        self.aircraft.retain(|_, aircraft| aircraft.time.elapsed() < AIRCRAFT_TIMEOUT);
+
+        Ok(self.aircraft.iter().map(|(callsign, aircraft)| SourceAircraft {
+            id: aircraft.id,
+            callsign: callsign.clone(),
+            lat: aircraft.lat,
+            lon: aircraft.lon,
+            altitude: SourceAltitude::True(aircraft.alt),
+            gs: aircraft.gs,
+            hdg: aircraft.hdg,
+            vs: aircraft.vs,
+            pitch: aircraft.pitch,
+            bank: aircraft.bank,
+            on_ground: on_ground_near_field(&self.airports, aircraft.lat, aircraft.lon, aircraft.alt, aircraft.gs),
+            squawk: None,
+            ground_alt: None,
+            transponder_mode: TransponderMode::ModeC,
+            frequency: None,
+            departure: None,
+            flight_plan: None,
+            info: Some(AircraftInfo { title: aircraft.model.clone(), ..AircraftInfo::default() }),
+            time: aircraft.time,
+        }).collect())
+    }
+}
+
+/// The callsign and position from a position message, or `None` for anything else.
+fn decode_position_message(message: &[u8]) -> Option<(String, FlightGearAircraft)> {
+    if message.len() < HEADER_LEN + POSITION_LEN { return None }
+    let u32_at = |offset: usize| u32::from_be_bytes(message[offset..offset + 4].try_into().unwrap());
+    let f32_at = |offset: usize| f32::from_be_bytes(message[offset..offset + 4].try_into().unwrap()) as f64;
+    let f64_at = |offset: usize| f64::from_be_bytes(message[offset..offset + 8].try_into().unwrap());
+    if u32_at(0) != MAGIC || u32_at(4) >> 16 != PROTOCOL_MAJOR_VERSION || u32_at(8) != POSITION_MESSAGE_ID { return None }
+
+    let callsign = c_string(&message[24..24 + CALLSIGN_LEN]).to_uppercase();
+    if callsign.is_empty() { return None }
+    let body = HEADER_LEN;
+    let model = c_string(&message[body..body + MODEL_LEN]);
+    // Skipping the time and lag
+    let position = [f64_at(body + 112), f64_at(body + 120), f64_at(body + 128)];
+    let orientation = [f32_at(body + 136), f32_at(body + 140), f32_at(body + 144)];
+    let velocity = [f32_at(body + 148), f32_at(body + 152), f32_at(body + 156)];
+
+    let (lat, lon, height) = geo::ecef_to_geodetic(position[0], position[1], position[2]);
+    // The orientation relative to the local horizon, as FlightGear works it out for its own AI
+    let horizontal = Quat::from_lon_lat(lon.to_radians(), lat.to_radians()).conjugate().multiply(&Quat::from_angle_axis(orientation));
+    let (hdg, pitch, bank) = horizontal.euler_degrees();
+    // North, east and down
+    let local_velocity = horizontal.back_transform(velocity);
+
+    let aircraft = FlightGearAircraft {
+        id: 0,
+        // Such as `Aircraft/c172p/Models/c172p.xml`
+        model: Path::new(&model).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or(model),
+        lat,
+        lon,
+        alt: altimetry::metres_to_feet(height),
+        hdg,
+        pitch,
+        bank,
+        gs: local_velocity[0].hypot(local_velocity[1]) * KNOTS_PER_METRE_PER_SECOND,
+        vs: altimetry::metres_to_feet(-local_velocity[2]) * 60.0,
+        time: Instant::now(),
+    };
+    Some((callsign, aircraft))
+}
+
+fn c_string(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|byte| *byte == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).trim().to_owned()
+}
+
+
+/// The bits of SimGear's quaternion needed to read an orientation, stored as x, y, z, w.
+#[derive(Debug, Clone, Copy)]
+struct Quat([f64; 4]);
+
+impl Quat {
+    /// The rotation from earth-centred axes to north, east and down at a point.
+    fn from_lon_lat(lon: f64, lat: f64) -> Quat {
+        let (zd2, yd2) = (0.5 * lon, -0.25 * std::f64::consts::PI - 0.5 * lat);
+        let (szd2, czd2, syd2, cyd2) = (zd2.sin(), zd2.cos(), yd2.sin(), yd2.cos());
+        Quat([-szd2 * syd2, czd2 * syd2, szd2 * cyd2, czd2 * cyd2])
+    }
+
+    /// A rotation by the length of `axis` in radians about its direction.
+    fn from_angle_axis(axis: [f64; 3]) -> Quat {
+        let angle = (axis[0] * axis[0] + axis[1] * axis[1] + axis[2] * axis[2]).sqrt();
+        if angle < 1e-9 { return Quat([0.0, 0.0, 0.0, 1.0]) }
+        let scale = (0.5 * angle).sin() / angle;
+        Quat([axis[0] * scale, axis[1] * scale, axis[2] * scale, (0.5 * angle).cos()])
+    }
+
+    fn conjugate(&self) -> Quat {
+        let [x, y, z, w] = self.0;
+        Quat([-x, -y, -z, w])
+    }
+
+    fn multiply(&self, other: &Quat) -> Quat {
+        let [x1, y1, z1, w1] = self.0;
+        let [x2, y2, z2, w2] = other.0;
+        Quat([
+            w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+            w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+            w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+            w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+        ])
+    }
+
+    /// Heading from 0 to 360, pitch and bank, in degrees.
+    fn euler_degrees(&self) -> (f64, f64, f64) {
+        let [x, y, z, w] = self.0;
+        let bank = (2.0 * (y * z + w * x)).atan2(w * w - x * x - y * y + z * z);
+        let pitch = -(2.0 * (x * z - w * y)).clamp(-1.0, 1.0).asin();
+        let hdg = (2.0 * (x * y + w * z)).atan2(w * w + x * x - y * y - z * z);
+        (hdg.to_degrees().rem_euclid(360.0), pitch.to_degrees(), bank.to_degrees())
+    }
+
+    /// Turns a vector in the rotated frame back into the frame the rotation started from.
+    fn back_transform(&self, v: [f64; 3]) -> [f64; 3] {
+        let [x, y, z, w] = self.0;
+        let r = 2.0 / (x * x + y * y + z * z + w * w);
+        let dot = x * v[0] + y * v[1] + z * v[2];
+        let cross = [y * v[2] - z * v[1], z * v[0] - x * v[2], x * v[1] - y * v[0]];
+        let scale = r * w * w - 1.0;
+        [
+            scale * v[0] + r * dot * x + r * w * cross[0],
+            scale * v[1] + r * dot * y + r * w * cross[1],
+            scale * v[2] + r * dot * z + r * w * cross[2],
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUBLIN: (f64, f64, f64) = (53.4213, -6.2701, 1500.0);
+
+    /// SimGear's `SGGeodesy::SGGeodToCart`, to place a test aircraft.
+    fn geodetic_to_ecef(lat: f64, lon: f64, height: f64) -> [f64; 3] {
+        let (a, f) = (6_378_137.0, 1.0 / 298.257223563);
+        let e2 = f * (2.0 - f);
+        let (lat, lon) = (lat.to_radians(), lon.to_radians());
+        let n = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        [(n + height) * lat.cos() * lon.cos(), (n + height) * lat.cos() * lon.sin(), (n * (1.0 - e2) + height) * lat.sin()]
+    }
+
+    /// SimGear's `SGQuat::fromYawPitchRoll`.
+    fn from_euler_degrees(hdg: f64, pitch: f64, bank: f64) -> Quat {
+        let (zd2, yd2, xd2) = (0.5 * hdg.to_radians(), 0.5 * pitch.to_radians(), 0.5 * bank.to_radians());
+        let (szd2, czd2, syd2, cyd2, sxd2, cxd2) = (zd2.sin(), zd2.cos(), yd2.sin(), yd2.cos(), xd2.sin(), xd2.cos());
+        Quat([
+            sxd2 * cyd2 * czd2 - cxd2 * syd2 * szd2,
+            cxd2 * syd2 * czd2 + sxd2 * cyd2 * szd2,
+            cxd2 * cyd2 * szd2 - sxd2 * syd2 * czd2,
+            cxd2 * cyd2 * czd2 + sxd2 * syd2 * szd2,
+        ])
+    }
+
+    fn angle_axis(quat: &Quat) -> [f64; 3] {
+        let [x, y, z, w] = quat.0;
+        let (x, y, z, w) = if w < 0.0 { (-x, -y, -z, -w) } else { (x, y, z, w) };
+        let angle = 2.0 * w.min(1.0).acos();
+        let scale = angle / (0.5 * angle).sin();
+        [x * scale, y * scale, z * scale]
+    }
+
+    /// A position message for an aircraft at `position` with the given attitude and velocity in
+    /// the body frame (forward, right, down), the way FlightGear sends one.
+    fn position_message(callsign: &str, position: (f64, f64, f64), attitude: (f64, f64, f64), velocity: [f32; 3]) -> Vec<u8> {
+        let (lat, lon, height) = position;
+        let (hdg, pitch, bank) = attitude;
+        let mut message = Vec::new();
+        for value in [MAGIC, PROTOCOL_MAJOR_VERSION << 16 | 1, POSITION_MESSAGE_ID, (HEADER_LEN + POSITION_LEN) as u32, 100, 0] {
+            message.extend_from_slice(&value.to_be_bytes());
+        }
+        let mut field = |bytes: &[u8], len: usize| {
+            message.extend_from_slice(bytes);
+            message.resize(message.len() + len - bytes.len(), 0);
+        };
+        field(callsign.as_bytes(), CALLSIGN_LEN);
+        field(b"Aircraft/c172p/Models/c172p.xml", MODEL_LEN);
+        message.extend_from_slice(&1234.5f64.to_be_bytes());
+        message.extend_from_slice(&0.1f64.to_be_bytes());
+        for coordinate in geodetic_to_ecef(lat, lon, height) {
+            message.extend_from_slice(&coordinate.to_be_bytes());
+        }
+        let orientation = Quat::from_lon_lat(lon.to_radians(), lat.to_radians()).multiply(&from_euler_degrees(hdg, pitch, bank));
+        for value in angle_axis(&orientation).into_iter().map(|value| value as f32).chain(velocity) {
+            message.extend_from_slice(&value.to_be_bytes());
+        }
+        // Angular velocity and the accelerations
+        message.resize(HEADER_LEN + POSITION_LEN, 0);
+        message
+    }
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn converts_earth_centred_coordinates() {
+        let (lat, lon, height) = geo::ecef_to_geodetic(6_378_137.0, 0.0, 0.0);
+        assert_eq!((lat, lon), (0.0, 0.0));
+        assert_close(height, 0.0, 1e-6);
+
+        let (lat, lon, height) = DUBLIN;
+        let [x, y, z] = geodetic_to_ecef(lat, lon, height);
+        let converted = geo::ecef_to_geodetic(x, y, z);
+        assert_close(converted.0, lat, 1e-9);
+        assert_close(converted.1, lon, 1e-9);
+        assert_close(converted.2, height, 1e-3);
+    }
+
+    #[test]
+    fn decodes_position_and_attitude() {
+        let message = position_message("ei-abc", DUBLIN, (135.0, 5.0, -20.0), [0.0; 3]);
+        let (callsign, aircraft) = decode_position_message(&message).unwrap();
+        assert_eq!(callsign, "EI-ABC");
+        assert_eq!(aircraft.model, "c172p");
+        assert_close(aircraft.lat, DUBLIN.0, 1e-7);
+        assert_close(aircraft.lon, DUBLIN.1, 1e-7);
+        assert_close(aircraft.alt, altimetry::metres_to_feet(DUBLIN.2), 0.01);
+        // The orientation goes over the wire as single precision
+        assert_close(aircraft.hdg, 135.0, 1e-3);
+        assert_close(aircraft.pitch, 5.0, 1e-3);
+        assert_close(aircraft.bank, -20.0, 1e-3);
+        assert_eq!((aircraft.gs, aircraft.vs), (0.0, 0.0));
+
+        // Headings either side of north come out between 0 and 360
+        let (_, aircraft) = decode_position_message(&position_message("EI-ABC", DUBLIN, (350.0, 0.0, 0.0), [0.0; 3])).unwrap();
+        assert_close(aircraft.hdg, 350.0, 1e-3);
+    }
+
+    #[test]
+    fn decodes_velocity_in_the_local_frame() {
+        // Climbing at 500 feet a minute, level
+        let climb = -(500.0 / 60.0 / altimetry::metres_to_feet(1.0)) as f32;
+        let (_, aircraft) = decode_position_message(&position_message("EI-ABC", DUBLIN, (270.0, 0.0, 0.0), [100.0, 0.0, climb])).unwrap();
+        assert_close(aircraft.gs, 100.0 * KNOTS_PER_METRE_PER_SECOND, 0.01);
+        assert_close(aircraft.vs, 500.0, 0.1);
+
+        // Nose up in a climb, the same air path is all forward speed in the body frame
+        let (_, aircraft) = decode_position_message(&position_message("EI-ABC", DUBLIN, (270.0, 10.0, 0.0), [100.0, 0.0, 0.0])).unwrap();
+        assert_close(aircraft.gs, 100.0 * 10f64.to_radians().cos() * KNOTS_PER_METRE_PER_SECOND, 0.01);
+        assert_close(aircraft.vs, altimetry::metres_to_feet(100.0 * 10f64.to_radians().sin()) * 60.0, 1.0);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let message = position_message("EI-ABC", DUBLIN, (0.0, 0.0, 0.0), [0.0; 3]);
+        assert!(decode_position_message(&message[..HEADER_LEN + POSITION_LEN - 1]).is_none());
+
+        let mut chat = message.clone();
+        chat[8..12].copy_from_slice(&1u32.to_be_bytes());
+        assert!(decode_position_message(&chat).is_none());
+
+        let mut other_version = message.clone();
+        other_version[4..8].copy_from_slice(&(2u32 << 16).to_be_bytes());
+        assert!(decode_position_message(&other_version).is_none());
+
+        let mut no_callsign = message;
+        no_callsign[24..24 + CALLSIGN_LEN].fill(0);
+        assert!(decode_position_message(&no_callsign).is_none());
+    }
+}
@@ -0,0 +1,308 @@
+use std::{io, sync::{atomic::{AtomicBool, Ordering}, mpsc::{Receiver, Sender}, Arc}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+
+use fsd_interface::{messages::PilotPositionUpdateMessage, TransponderCode, TransponderMode};
+
+use crate::ui::{Message, Ui};
+
+use super::{airports::AirportDatabase, config::Config, dead_reckoning, enrichment::AircraftInfo, frequency::Frequency, fsuipc, metar::MetarProvider, sim_flight_plan::SimFlightPlan, squawk::CONSPICUITY_CODE, track::Track, vatsim::VatsimDataProvider, Preferences, Shared};
+
+use self::{flightgear::{FlightGearSource, DEFAULT_FLIGHTGEAR_PORT}, msfs::MsfsSource, relay::Relay, sbs::{SbsSource, DEFAULT_SBS_ADDRESS}, vatsim::VatsimSource, xplane::{XPlaneSource, DEFAULT_XPLANE_ADDRESS, DEFAULT_XPLANE_LISTEN_PORT}};
+
+mod relay;
+mod msfs;
+mod vatsim;
+mod sbs;
+mod xplane;
+mod flightgear;
+
+pub const FLIGHT_PLAN_RECIPIENT: &str = "A*";
+const QNH_STATION_MAX_DISTANCE_NM: f64 = 100.0;
+/// Extrapolated positions are not sent once they may be further than this from the truth.
+const MAX_EXTRAPOLATION_ERROR_NM: f64 = 0.5;
+const ON_GROUND_MAX_SPEED_KT: f64 = 40.0;
+/// Leaves room for sims and the network disagreeing with the airport file about field elevations.
+const ON_GROUND_MAX_HEIGHT_FT: f64 = 200.0;
+const ON_GROUND_FIELD_MAX_DISTANCE_NM: f64 = 5.0;
+
+
+
+/// Things other threads want done in the sim, which only the thread reading the source talks to.
+pub enum SimCommand {
+    SetSquawk(u16),
+    /// A text message from EuroScope, shown if it is to us or on our COM1 frequency.
+    ShowTextMessage { from: String, to: String, message: String },
+}
+
+/// Where traffic comes from, chosen with `type` in the `[source]` section. This is only read at
+/// startup, as changing it means starting a different thread.
+pub enum SourceKind {
+    /// MSFS through FSUIPC.
+    Msfs,
+    /// Pilots connected to VATSIM, straight from the data feed, so no sim is needed.
+    Vatsim,
+    /// Real-world ADS-B traffic from a BaseStation feed at `address`.
+    Sbs { address: String },
+    /// X-Plane, sending Data Output to `port` and taking dataref subscriptions at `address`.
+    XPlane { port: u16, address: String },
+    /// FlightGear multiplayer, sending position messages to `port`.
+    FlightGear { port: u16 },
+}
+
+impl SourceKind {
+    /// Defaults to the sim on Windows and the VATSIM feed elsewhere, where there is no FSUIPC.
+    pub fn from_config(config: &Config) -> SourceKind {
+        match config.get("source", "type").map(|kind| kind.to_lowercase()).as_deref() {
+            Some("msfs") => SourceKind::Msfs,
+            Some("vatsim") => SourceKind::Vatsim,
+            Some("sbs") => SourceKind::Sbs { address: config.get("source", "address").unwrap_or(DEFAULT_SBS_ADDRESS).to_owned() },
+            Some("xplane") => SourceKind::XPlane {
+                port: config.get_parsed("source", "port").unwrap_or(DEFAULT_XPLANE_LISTEN_PORT),
+                address: config.get("source", "address").unwrap_or(DEFAULT_XPLANE_ADDRESS).to_owned(),
+            },
+            Some("flightgear") => SourceKind::FlightGear { port: config.get_parsed("source", "port").unwrap_or(DEFAULT_FLIGHTGEAR_PORT) },
+            _ if cfg!(windows) => SourceKind::Msfs,
+            _ => SourceKind::Vatsim,
+        }
+    }
+
+    /// The source to pass to `source_thread`.
+    pub fn open(self, preferences: Preferences, vatsim_data_provider: VatsimDataProvider, airports: AirportDatabase) -> Box<dyn TrafficSource> {
+        match self {
+            SourceKind::Msfs => Box::new(MsfsSource::new(preferences)),
+            SourceKind::Vatsim => Box::new(VatsimSource::new(vatsim_data_provider, airports)),
+            SourceKind::Sbs { address } => Box::new(SbsSource::new(address)),
+            SourceKind::XPlane { port, address } => Box::new(XPlaneSource::new(port, address, airports)),
+            SourceKind::FlightGear { port } => Box::new(FlightGearSource::new(port, airports)),
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug)]
+pub enum SourceError {
+    /// Nothing has been received yet, or the source has stopped sending.
+    NoData,
+    Io(io::Error),
+    Sim(fsuipc::Error),
+}
+
+impl From<io::Error> for SourceError {
+    fn from(value: io::Error) -> Self {
+        SourceError::Io(value)
+    }
+}
+
+impl From<fsuipc::Error> for SourceError {
+    fn from(value: fsuipc::Error) -> Self {
+        SourceError::Sim(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SourceAltitude {
+    /// Above mean sea level, in feet.
+    True(f64),
+    /// Barometric, in feet, as transponders report it.
+    Pressure(f64),
+    /// Barometric, in feet, with the setting in hPa on the altimeter the pilot is reading.
+    Altimeter { pressure_alt: f64, setting_hpa: f64 },
+}
+
+/// An aircraft as reported by a traffic source.
+#[derive(Debug, Clone)]
+pub struct SourceAircraft {
+    /// Identifies the aircraft within the source, even if its callsign changes.
+    pub id: u32,
+    pub callsign: String,
+    pub lat: f64,
+    pub lon: f64,
+    /// The other kind of altitude is worked out from the nearest QNH.
+    pub altitude: SourceAltitude,
+    pub gs: f64,
+    pub hdg: f64,
+    pub vs: f64,
+    pub pitch: f64,
+    pub bank: f64,
+    pub on_ground: bool,
+    /// Elevation of the ground below, in feet, for sources that know it.
+    pub ground_alt: Option<f64>,
+    pub squawk: Option<u16>,
+    pub transponder_mode: TransponderMode,
+    /// The frequency tuned on COM1, for sources that know it.
+    pub frequency: Option<Frequency>,
+    /// Where the aircraft took off from, for sources that know it. Used to pick ORCAM squawks.
+    pub departure: Option<String>,
+    /// A flight plan from the source, sent once for aircraft that aren't on the network.
+    pub flight_plan: Option<SimFlightPlan>,
+    /// What the source knows about the aircraft itself, answered to controllers who ask.
+    pub info: Option<AircraftInfo>,
+    /// When the source last heard about the aircraft. Reports with the same time are not new.
+    pub time: Instant,
+}
+
+/// Somewhere aircraft positions can be read from, be it a sim or a live feed.
+pub trait TrafficSource: Send {
+    /// What the source is called in the log.
+    fn name(&self) -> String;
+
+    /// The aircraft the source knows about right now. Called about once a second, so it
+    /// shouldn't block for long.
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError>;
+
+    /// The aircraft being flown, for sources that have one. Its id and callsign are ignored.
+    fn own_aircraft(&self) -> Option<SourceAircraft> {
+        None
+    }
+
+    /// Whether the VATSIM data feed has to be fetched even if flight plans are turned off.
+    fn needs_vatsim_data(&self) -> bool {
+        false
+    }
+
+    /// Sets our own transponder, as assigned in EuroScope. Returns whether it was set.
+    fn set_squawk(&mut self, _code: u16) -> bool {
+        false
+    }
+
+    /// Shows the pilot a text message sent to us, for sources that can.
+    fn show_text_message(&mut self, _from: &str, _message: &str) {}
+
+    /// Called when an aircraft hasn't been heard from for long enough that its track is
+    /// dropped, so anything else kept about it can go too.
+    fn forget(&mut self, _id: u32) {}
+
+    /// What the UI is told when traffic starts coming in.
+    fn connected_message(&self) -> Message {
+        Message::SourceConnected(self.name())
+    }
+
+    /// What the UI is told when traffic stops coming in.
+    fn disconnected_message(&self) -> Message {
+        Message::SourceDisconnected
+    }
+}
+
+
+/// Relays traffic from `source` to EuroScope, and carries out commands from it in the sim.
+pub fn source_thread<U: Ui + 'static>(should_terminate: Arc<AtomicBool>, shared: Shared, ui_link: U, mut source: Box<dyn TrafficSource>, sim_commands: Receiver<SimCommand>, msg_sender: Sender<String>) -> JoinHandle<()> {
+    thread::Builder::new().name("TrafficViewerSourceThread".into()).spawn(move || {
+
+        let preferences = shared.preferences.clone();
+        let mut relay = Relay::new(shared, ui_link.clone(), msg_sender);
+        let mut source_connected = false;
+        for i in 0..usize::MAX {
+            if should_terminate.load(Ordering::Relaxed) { break };
+
+            let use_vatsim_data = preferences.fetch_flight_plans() || source.needs_vatsim_data();
+            let cycle = relay.begin_cycle(i, use_vatsim_data);
+            while let Ok(command) = sim_commands.try_recv() {
+                relay.handle_command(command, source.as_mut());
+            }
+
+            let polled = source.poll();
+            if polled.is_ok() != source_connected {
+                source_connected = polled.is_ok();
+                ui_link.dispatch_message(if source_connected { source.connected_message() } else { source.disconnected_message() });
+            }
+            if let Ok(aircraft_list) = polled {
+                for aircraft in aircraft_list {
+                    relay.relay_traffic(&cycle, aircraft);
+                }
+                if let Some(own_aircraft) = source.own_aircraft() {
+                    relay.relay_own_aircraft(&cycle, own_aircraft);
+                }
+            }
+            // While the source is down too, so its traffic times out rather than staying frozen on the scope
+            for id in relay.prune() {
+                source.forget(id);
+            }
+
+            if source_connected && !cycle.position_update_due && preferences.extrapolate_positions() {
+                relay.extrapolate();
+            }
+
+            thread::sleep(Duration::from_secs(1));
+        }
+    }).unwrap()
+}
+
+
+fn refresh_metars<U: Ui>(i: usize, preferences: &Preferences, metar_provider: &mut MetarProvider, ui_link: &U) {
+    let metar_refresh_due = preferences.fetch_metars() && if metar_provider.last_update_successful() { i.is_multiple_of(120) } else { true };
+    if metar_refresh_due {
+        let message = if metar_provider.update() {
+            Message::MetarsRetrieved
+        } else {
+            Message::MetarsDisconnected
+        };
+        ui_link.dispatch_message(message);
+    }
+}
+
+fn refresh_vatsim_data<U: Ui>(i: usize, enabled: bool, vatsim_data_provider: &mut VatsimDataProvider, ui_link: &U) {
+    let vatsim_data_refresh_due = enabled && if vatsim_data_provider.last_update_successful() { i.is_multiple_of(15) } else { true };
+    if vatsim_data_refresh_due {
+        let message = if vatsim_data_provider.update() {
+            Message::VatsimDataRetrieved
+        } else {
+            Message::VatsimDataDisconnected
+        };
+        ui_link.dispatch_message(message);
+    }
+}
+
+fn position_update(track: &Track) -> PilotPositionUpdateMessage {
+    let sample = &track.latest;
+    let transponder_code = TransponderCode::try_from(track.squawk).unwrap_or(TransponderCode::try_from(CONSPICUITY_CODE).unwrap());
+    PilotPositionUpdateMessage::new(&track.callsign, track.transponder_mode, transponder_code, track.rating.into(), sample.lat, sample.lon, sample.true_alt, sample.pressure_alt, sample.gs as u32, sample.pitch, sample.bank, sample.hdg.floor(), sample.on_ground)
+}
+
+/// A dead-reckoned position update to fill the gap between samples, unless it may be too far out.
+fn extrapolated_position_update(track: &Track, now: Instant) -> Option<PilotPositionUpdateMessage> {
+    if dead_reckoning::error_bound_nm(track, now) > MAX_EXTRAPOLATION_ERROR_NM { return None }
+    let sample = dead_reckoning::extrapolate(track, now)?;
+    let predicted = Track { latest: sample, ..track.clone() };
+    Some(position_update(&predicted))
+}
+
+/// The QNH from the METAR of the nearest station that has one.
+fn nearest_qnh(airports: &AirportDatabase, metar_provider: &MetarProvider, lat: f64, lon: f64) -> Option<f64> {
+    let (station, _) = airports.nearest(lat, lon, QNH_STATION_MAX_DISTANCE_NM, |airport| metar_provider.lookup_qnh(&airport.icao).is_some())?;
+    metar_provider.lookup_qnh(&station.icao)
+}
+
+/// Guesses whether an aircraft is on the ground, for sources that don't say. Going slowly isn't
+/// enough, as helicopters and anything in slow flight are airborne. Until the airport database
+/// has loaded, or if it has no elevation for the field, only the speed is used.
+fn on_ground_near_field(airports: &AirportDatabase, lat: f64, lon: f64, true_alt: f64, gs: f64) -> bool {
+    if gs >= ON_GROUND_MAX_SPEED_KT { return false }
+    if !airports.is_loaded() { return true }
+    match airports.nearest(lat, lon, ON_GROUND_FIELD_MAX_DISTANCE_NM, |_| true) {
+        Some((field, _)) => field.elevation_ft.is_none_or(|elevation| true_alt - elevation < ON_GROUND_MAX_HEIGHT_FT),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::airports::Airport;
+
+    #[test]
+    fn only_slow_aircraft_down_at_the_field_are_on_the_ground() {
+        let airports = AirportDatabase::with_airports(vec![
+            Airport { icao: String::from("LSZH"), lat: 47.4647, lon: 8.5492, elevation_ft: Some(1416.0) },
+        ]);
+        assert!(on_ground_near_field(&airports, 47.46, 8.55, 1420.0, 15.0));
+        assert!(!on_ground_near_field(&airports, 47.46, 8.55, 1420.0, 140.0));
+        // A helicopter hovering over the field, and one slowly crossing open country
+        assert!(!on_ground_near_field(&airports, 47.46, 8.55, 2400.0, 15.0));
+        assert!(!on_ground_near_field(&airports, 46.5, 8.0, 1420.0, 15.0));
+    }
+
+    #[test]
+    fn goes_by_speed_until_the_airports_have_loaded() {
+        assert!(on_ground_near_field(&AirportDatabase::new(), 47.46, 8.55, 2400.0, 15.0));
+        assert!(!on_ground_near_field(&AirportDatabase::new(), 47.46, 8.55, 1420.0, 140.0));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::HashMap, ffi::CStr, time::{Duration, Instant}};
+
+use fsd_interface::TransponderMode;
+
+use super::{SourceAircraft, SourceAltitude, SourceError, TrafficSource};
+use crate::{core::{enrichment::EnrichmentCache, frequency::Frequency, fsuipc::{self, AiRoute, OwnAircraftData}, sim_flight_plan::SimFlightPlan, Preferences}, ui::Message};
+
+/// TCAS headings are in 65536ths of a turn.
+const HDG_FACTOR: f64 = 65536.0 / 360.0;
+/// How long each poll may spend collecting aircraft info from the sim.
+const ENRICHMENT_TIME_BUDGET: Duration = Duration::from_millis(100);
+const TEXT_MESSAGE_DISPLAY_SECS: i16 = 15;
+/// AI routes hardly ever change, so they are read less often than positions.
+const ROUTE_REFRESH_INTERVAL: Duration = Duration::from_secs(4);
+
+
+
+/// MSFS through FSUIPC: AI and multiplayer traffic from the TCAS tables, and our own aircraft.
+pub struct MsfsSource {
+    preferences: Preferences,
+    linked: bool,
+    ai_routes: HashMap<u32, AiRoute>,
+    routes_read: Option<Instant>,
+    enrichment: EnrichmentCache,
+    own: Option<SourceAircraft>,
+}
+
+impl MsfsSource {
+    pub fn new(preferences: Preferences) -> MsfsSource {
+        MsfsSource {
+            preferences,
+            linked: false,
+            ai_routes: HashMap::new(),
+            routes_read: None,
+            enrichment: EnrichmentCache::new(),
+            own: None,
+        }
+    }
+
+    fn read(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        if self.routes_read.is_none_or(|read| read.elapsed() >= ROUTE_REFRESH_INTERVAL) {
+            if let Ok(routes) = fsuipc::get_ai_routes(true).and_then(|ground_routes| fsuipc::get_ai_routes(false).map(|airborne_routes| ground_routes.into_iter().chain(airborne_routes))) {
+                self.ai_routes = routes.collect();
+            }
+            self.routes_read = Some(Instant::now());
+        }
+
+        let tcas_range = self.preferences.area_filter().tcas_range_nm();
+        let level_filter = self.preferences.level_filter();
+        // Don't bother reading a table the filter would empty anyway
+        let read_tcas_table = |on_ground| if level_filter.may_include(on_ground) { fsuipc::get_aircraft(on_ground, tcas_range) } else { Ok(Vec::new()) };
+        let ground_aircraft = read_tcas_table(true)?;
+        let airborne_aircraft = read_tcas_table(false)?;
+        let now = Instant::now();
+        self.own = Some(own_aircraft(&fsuipc::get_own_aircraft_data()?, now));
+
+        let aircraft_list: Vec<_> = ground_aircraft.into_iter().map(|ac| (ac, true)).chain(airborne_aircraft.into_iter().map(|ac| (ac, false))).collect();
+        for (tcas_data, _) in &aircraft_list {
+            self.enrichment.request(tcas_data.id);
+        }
+        self.enrichment.poll(ENRICHMENT_TIME_BUDGET).ok();
+
+        Ok(aircraft_list.into_iter().filter_map(|(tcas_data, from_ground_table)| {
+            let callsign = CStr::from_bytes_until_nul(&tcas_data.atc_id).ok()?.to_str().ok()?;
+            let info = self.enrichment.get(tcas_data.id).cloned();
+            let route = self.ai_routes.get(&tcas_data.id);
+            Some(SourceAircraft {
+                id: tcas_data.id,
+                callsign: callsign.to_owned(),
+                lat: tcas_data.lat as f64,
+                lon: tcas_data.lon as f64,
+                altitude: SourceAltitude::True(tcas_data.alt as f64),
+                gs: tcas_data.gs as f64,
+                hdg: tcas_data.hdg as f64 / HDG_FACTOR,
+                vs: tcas_data.vs as f64,
+                pitch: 0.0,
+                bank: 0.0,
+                on_ground: tcas_data.on_ground(from_ground_table),
+                // TCAS doesn't say how high the ground is under traffic
+                ground_alt: None,
+                squawk: None,
+                transponder_mode: TransponderMode::ModeC,
+                frequency: Frequency::from_bcd(tcas_data.com1),
+                departure: route.and_then(|route| route.departure.clone()),
+                // Only made once we know what the aircraft is, as it is sent just the once
+                flight_plan: info.as_ref().map(|info| SimFlightPlan::new(info, route)),
+                info,
+                time: now,
+            })
+        }).collect())
+    }
+}
+
+impl TrafficSource for MsfsSource {
+    fn name(&self) -> String {
+        String::from("MSFS")
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        if !self.linked {
+            fsuipc::link(None)?;
+            self.linked = true;
+        }
+        let result = self.read();
+        if result.is_err() {
+            self.linked = false;
+            self.own = None;
+        }
+        result
+    }
+
+    fn own_aircraft(&self) -> Option<SourceAircraft> {
+        self.own.clone()
+    }
+
+    fn set_squawk(&mut self, code: u16) -> bool {
+        self.linked && fsuipc::set_squawk(code).is_ok()
+    }
+
+    fn show_text_message(&mut self, from: &str, message: &str) {
+        if self.linked {
+            fsuipc::show_message(&format!("{}: {}", from, message), TEXT_MESSAGE_DISPLAY_SECS).ok();
+        }
+    }
+
+    fn forget(&mut self, id: u32) {
+        self.ai_routes.remove(&id);
+        self.enrichment.remove(id);
+    }
+
+    fn connected_message(&self) -> Message {
+        Message::MsfsConnected
+    }
+
+    fn disconnected_message(&self) -> Message {
+        Message::MsfsDisconnected
+    }
+}
+
+fn own_aircraft(data: &OwnAircraftData, time: Instant) -> SourceAircraft {
+    let transponder_mode = if !data.transponder_state.is_replying() {
+        TransponderMode::Standby
+    } else if data.ident {
+        TransponderMode::Ident
+    } else {
+        TransponderMode::ModeC
+    };
+    SourceAircraft {
+        id: 0,
+        callsign: String::new(),
+        lat: data.lat,
+        lon: data.lon,
+        altitude: SourceAltitude::Altimeter { pressure_alt: data.pressure_alt, setting_hpa: data.altimeter_setting_hpa },
+        gs: data.gs,
+        hdg: data.true_hdg,
+        vs: data.vs,
+        pitch: data.pitch,
+        bank: data.bank,
+        on_ground: data.on_ground,
+        ground_alt: Some(data.ground_alt),
+        squawk: Some(data.squawk),
+        transponder_mode,
+        frequency: data.com1,
+        departure: None,
+        flight_plan: None,
+        info: None,
+        time,
+    }
+}
@@ -0,0 +1,487 @@
+use std::{collections::HashSet, mem, sync::mpsc::Sender, time::Instant};
+
+use fsd_interface::{messages::{FlightPlanMessage, PilotDeregisterMessage}, TransponderMode};
+
+use crate::{core::{airports::AirportDatabase, altimetry::{self, STANDARD_PRESSURE_HPA}, callsign_map::CallsignMap, correlation::{CorrelationSettings, Correlator, Observation}, enrichment::AircraftInfoProvider, fast_position::FastPositionMessage, filter::{Area, CallsignFilter, LevelFilter}, frequency::{Frequency, FrequencyProvider}, metar::MetarProvider, sim_flight_plan::SimFlightPlanProvider, squawk::{SquawkAllocator, CONSPICUITY_CODE}, track::{Track, TrackSample, TrackStore}, vatsim::VatsimDataProvider, Preferences, Shared}, ui::{Message, Ui}};
+
+use super::{extrapolated_position_update, nearest_qnh, position_update, refresh_metars, refresh_vatsim_data, SimCommand, SourceAircraft, SourceAltitude, TrafficSource, FLIGHT_PLAN_RECIPIENT};
+
+
+
+/// Settings read once a cycle, so that every aircraft in it is treated the same.
+pub struct Cycle {
+    pub position_update_due: bool,
+    pub fast_position_update_due: bool,
+    use_vatsim_data: bool,
+    area: Area,
+    level_filter: LevelFilter,
+    callsign_filter: CallsignFilter,
+    callsign_map: CallsignMap,
+    correlation_settings: CorrelationSettings,
+    frequency_filter: Option<Frequency>,
+    my_callsign: String,
+}
+
+/// Turns what a traffic source reports into tracks and sends them to EuroScope. Everything
+/// here is the same whichever source the aircraft came from.
+pub struct Relay<U: Ui> {
+    preferences: Preferences,
+    ui_link: U,
+    metar_provider: MetarProvider,
+    vatsim_data_provider: VatsimDataProvider,
+    airports: AirportDatabase,
+    sim_flight_plan_provider: SimFlightPlanProvider,
+    aircraft_info_provider: AircraftInfoProvider,
+    frequency_provider: FrequencyProvider,
+    msg_sender: Sender<String>,
+
+    tracks: TrackStore,
+    own_track: Option<Track>,
+    own_aircraft_hidden: bool,
+    last_callsign_sent: String,
+    last_own_transponder: Option<(u16, mem::Discriminant<TransponderMode>)>,
+    squawk_allocator: SquawkAllocator,
+    correlator: Correlator,
+    // Ids we have already sent a flight plan from the source for
+    sim_flight_plan_ids: HashSet<u32>,
+    // Ids that made it through the filters this cycle, the only ones worth extrapolating
+    relayed_ids: HashSet<u32>,
+}
+
+impl<U: Ui> Relay<U> {
+    pub fn new(shared: Shared, ui_link: U, msg_sender: Sender<String>) -> Relay<U> {
+        let Shared { preferences, metar_provider, vatsim_data_provider, airports, sim_flight_plan_provider, aircraft_info_provider, frequency_provider } = shared;
+        Relay {
+            squawk_allocator: SquawkAllocator::new(preferences.squawk_settings()),
+            preferences,
+            ui_link,
+            metar_provider,
+            vatsim_data_provider,
+            airports,
+            sim_flight_plan_provider,
+            aircraft_info_provider,
+            frequency_provider,
+            msg_sender,
+            tracks: TrackStore::new(),
+            own_track: None,
+            own_aircraft_hidden: false,
+            last_callsign_sent: String::new(),
+            last_own_transponder: None,
+            correlator: Correlator::new(),
+            sim_flight_plan_ids: HashSet::new(),
+            relayed_ids: HashSet::new(),
+        }
+    }
+
+    /// Fetches METARs and VATSIM data when they are due, and reads the settings for cycle `i`.
+    pub fn begin_cycle(&mut self, i: usize, use_vatsim_data: bool) -> Cycle {
+        refresh_metars(i, &self.preferences, &mut self.metar_provider, &self.ui_link);
+        refresh_vatsim_data(i, use_vatsim_data, &mut self.vatsim_data_provider, &self.ui_link);
+        self.squawk_allocator.update_settings(self.preferences.squawk_settings());
+        self.relayed_ids.clear();
+
+        let fast_position_interval = self.preferences.fast_position_interval();
+        let own_position = self.own_track.as_ref().map(|track| (track.latest.lat, track.latest.lon));
+        Cycle {
+            position_update_due: i.is_multiple_of(4),
+            fast_position_update_due: fast_position_interval > 0 && i.is_multiple_of(fast_position_interval),
+            use_vatsim_data,
+            area: self.preferences.area_filter().resolve(own_position, &self.airports),
+            level_filter: self.preferences.level_filter(),
+            callsign_filter: self.preferences.callsign_filter(),
+            callsign_map: self.preferences.callsign_map(),
+            correlation_settings: self.preferences.correlation_settings(),
+            frequency_filter: self.preferences.frequency_filter(),
+            my_callsign: self.preferences.pilot_callsign(),
+        }
+    }
+
+    pub fn handle_command(&mut self, command: SimCommand, source: &mut dyn TrafficSource) {
+        match command {
+            SimCommand::SetSquawk(code) => if source.set_squawk(code) {
+                self.ui_link.dispatch_message(Message::SquawkSet(code));
+            },
+            SimCommand::ShowTextMessage { from, to, message } => {
+                let to_us = to == self.preferences.pilot_callsign();
+                let own_frequency = self.own_track.as_ref().and_then(|track| track.frequency);
+                let on_our_frequency = Frequency::from_fsd(&to).zip(own_frequency).is_some_and(|(frequency, com1)| frequency.matches(com1));
+                if !to_us && !on_our_frequency { return }
+                source.show_text_message(&from, &message);
+                self.ui_link.dispatch_message(Message::TextMessageReceived(from, message));
+            },
+        }
+    }
+
+    pub fn relay_traffic(&mut self, cycle: &Cycle, aircraft: SourceAircraft) {
+        if !cycle.area.contains(aircraft.lat, aircraft.lon) {
+            self.drop_track(aircraft.id);
+            return;
+        }
+
+        // Looked up once, as both the correlation and the sample need it
+        let metar_qnh = nearest_qnh(&self.airports, &self.metar_provider, aircraft.lat, aircraft.lon);
+        let mut network_callsign = cycle.callsign_map.resolve(&aircraft.callsign, aircraft.info.as_ref(), |candidate| self.vatsim_data_provider.has_aircraft(candidate));
+        if cycle.use_vatsim_data && !self.vatsim_data_provider.has_aircraft(&network_callsign) {
+            // Not known by any name, so look for a pilot flying in the same place
+            let observation = Observation { lat: aircraft.lat, lon: aircraft.lon, alt: self.sample(&aircraft, metar_qnh).true_alt, gs: aircraft.gs };
+            let is_taken = |candidate: &str| candidate == cycle.my_callsign || self.tracks.iter().any(|(id, track)| *id != aircraft.id && track.callsign == candidate);
+            if let Some((correlation, is_new)) = self.correlator.correlate(aircraft.id, observation, &cycle.correlation_settings, &self.vatsim_data_provider, is_taken) {
+                if is_new {
+                    let confidence = (correlation.confidence * 100.0).round() as u8;
+                    self.ui_link.dispatch_message(Message::AircraftCorrelated(aircraft.callsign.clone(), correlation.callsign.clone(), confidence));
+                }
+                network_callsign = correlation.callsign;
+            }
+        } else {
+            self.correlator.release(aircraft.id);
+        }
+        let callsign = network_callsign.as_str();
+
+        if !cycle.callsign_filter.allows(callsign) {
+            self.drop_track(aircraft.id);
+            return;
+        }
+        if let Some(frequency_filter) = cycle.frequency_filter {
+            if !aircraft.frequency.is_some_and(|frequency| frequency.matches(frequency_filter)) {
+                self.drop_track(aircraft.id);
+                return;
+            }
+        }
+
+        // Only take the pending flight plan when a full position goes out with it
+        let vatsim_details = if !cycle.use_vatsim_data {
+            None
+        } else if cycle.position_update_due {
+            self.vatsim_data_provider.get_details_and_flight_plan_to_send(callsign)
+        } else {
+            self.vatsim_data_provider.get_aircraft_details(callsign).map(|details| (details, None))
+        };
+        if vatsim_details.is_none() && self.preferences.only_show_vatsim() {
+            self.drop_track(aircraft.id);
+            return;
+        }
+
+        let network_qnh = vatsim_details.as_ref().map(|(details, _)| altimetry::in_hg_to_hpa(details.qnh_i_hg as f64));
+        let sample = self.sample(&aircraft, metar_qnh.or(network_qnh));
+        if !cycle.level_filter.allows(sample.on_ground, sample.pressure_alt) {
+            self.drop_track(aircraft.id);
+            return;
+        }
+
+        let previous_callsign = self.tracks.get(aircraft.id).map(|track| track.callsign.clone()).filter(|previous| previous != callsign);
+        if let Some(previous_callsign) = previous_callsign.as_deref() {
+            // Known by a new name now, so take the old one off the scope
+            self.deregister(previous_callsign);
+            if self.sim_flight_plan_ids.remove(&aircraft.id) {
+                self.sim_flight_plan_provider.remove(previous_callsign);
+            }
+        }
+        // Sources that report less often than we poll give the same sample more than once
+        if self.tracks.get(aircraft.id).is_none_or(|track| track.latest.time != sample.time || previous_callsign.is_some()) {
+            self.tracks.update(aircraft.id, callsign, sample);
+        }
+        let Some(track) = self.tracks.get_mut(aircraft.id) else { return };
+        self.relayed_ids.insert(aircraft.id);
+        track.frequency = aircraft.frequency;
+        self.frequency_provider.set(callsign, track.frequency);
+        if let Some(info) = aircraft.info {
+            self.aircraft_info_provider.insert(callsign, info.clone());
+            track.info = Some(info);
+        }
+        track.squawk = aircraft.squawk
+            .or_else(|| vatsim_details.as_ref().map(|(details, _)| details.transponder.parse::<u16>().unwrap_or(CONSPICUITY_CODE)))
+            .unwrap_or_else(|| self.squawk_allocator.assign(aircraft.id, aircraft.departure.as_deref()));
+        track.rating = vatsim_details.as_ref().map(|(details, _)| details.fsd_pilot_rating()).unwrap_or_else(|| self.preferences.default_pilot_rating());
+
+        if cycle.fast_position_update_due {
+            let fast_position = FastPositionMessage::from_track(track, agl_alt(&sample, aircraft.ground_alt));
+            self.msg_sender.send(fast_position.to_string()).ok();
+        }
+        if !cycle.position_update_due { return }
+
+        let pos_rep = position_update(track);
+        let fp_update = match vatsim_details {
+            Some((_, flight_plan)) => flight_plan.map(fsd_interface::FlightPlan::from),
+            // Aircraft not on the network get one plan from the source, if it can make one
+            None => match aircraft.flight_plan {
+                Some(mut flight_plan) if self.sim_flight_plan_ids.insert(aircraft.id) => {
+                    flight_plan.check_airports(&self.airports);
+                    self.sim_flight_plan_provider.insert(callsign, flight_plan.clone());
+                    Some(fsd_interface::FlightPlan::from(flight_plan))
+                },
+                _ => None,
+            },
+        }.map(|fp| FlightPlanMessage::new(FLIGHT_PLAN_RECIPIENT, callsign, fp));
+
+        self.msg_sender.send(pos_rep.to_string()).ok();
+        if let Some(flight_plan) = fp_update.map(|fp| fp.to_string()) {
+            self.msg_sender.send(flight_plan).ok();
+        }
+    }
+
+    /// Our own aircraft goes out under our callsign, wherever it is, unless the level filter hides it.
+    pub fn relay_own_aircraft(&mut self, cycle: &Cycle, aircraft: SourceAircraft) {
+        let my_callsign = cycle.my_callsign.as_str();
+        if !self.last_callsign_sent.is_empty() && self.last_callsign_sent != my_callsign {
+            let last_callsign_sent = mem::take(&mut self.last_callsign_sent);
+            self.deregister(&last_callsign_sent);
+        }
+        self.last_callsign_sent = my_callsign.to_owned();
+
+        let sample = self.sample(&aircraft, nearest_qnh(&self.airports, &self.metar_provider, aircraft.lat, aircraft.lon));
+        let own_aircraft_shown = cycle.level_filter.allows(sample.on_ground, sample.pressure_alt);
+        if !own_aircraft_shown && !self.own_aircraft_hidden {
+            let dc = PilotDeregisterMessage::new(my_callsign, "1000000");
+            self.msg_sender.send(dc.to_string()).ok();
+        }
+        // Make sure a position goes out straight away when we reappear
+        let reappeared = own_aircraft_shown && self.own_aircraft_hidden;
+        self.own_aircraft_hidden = !own_aircraft_shown;
+
+        let own_track = match self.own_track.as_mut() {
+            Some(track) => {
+                track.update(my_callsign, sample);
+                track
+            },
+            None => self.own_track.insert(Track::new(my_callsign.to_owned(), sample)),
+        };
+        own_track.squawk = aircraft.squawk.unwrap_or(CONSPICUITY_CODE);
+        own_track.transponder_mode = aircraft.transponder_mode;
+        own_track.frequency = aircraft.frequency;
+        self.frequency_provider.set(my_callsign, own_track.frequency);
+        // Send a squawk change or ident straight away rather than waiting for the next position update
+        let transponder = (own_track.squawk, mem::discriminant(&own_track.transponder_mode));
+        let transponder_changed = self.last_own_transponder.replace(transponder) != Some(transponder);
+        if !own_aircraft_shown { return }
+
+        if cycle.fast_position_update_due {
+            let fast_position = FastPositionMessage::from_track(own_track, agl_alt(&sample, aircraft.ground_alt));
+            self.msg_sender.send(fast_position.to_string()).ok();
+        }
+        if !cycle.position_update_due && !transponder_changed && !reappeared { return }
+
+        let vatsim_details = if cycle.use_vatsim_data && cycle.position_update_due {
+            self.vatsim_data_provider.get_details_and_flight_plan_to_send(my_callsign)
+        } else {
+            None
+        };
+        if cycle.position_update_due {
+            own_track.rating = vatsim_details.as_ref().map(|(details, _)| details.fsd_pilot_rating()).unwrap_or_else(|| self.preferences.default_pilot_rating());
+        }
+        let pos_rep = position_update(own_track);
+        let fp_update = vatsim_details.and_then(|(_, flight_plan)| flight_plan).map(|fp| {
+            FlightPlanMessage::new(FLIGHT_PLAN_RECIPIENT, my_callsign, fsd_interface::FlightPlan::from(fp))
+        });
+
+        self.msg_sender.send(pos_rep.to_string()).ok();
+        if let Some(flight_plan) = fp_update.map(|fp| fp.to_string()) {
+            self.msg_sender.send(flight_plan).ok();
+        }
+    }
+
+    /// Takes tracks that haven't been heard from for a while off the scope, returning their ids.
+    pub fn prune(&mut self) -> Vec<u32> {
+        self.tracks.prune().into_iter().map(|(id, track)| {
+            self.deregister(&track.callsign);
+            self.release(id, &track);
+            id
+        }).collect()
+    }
+
+    /// Takes an aircraft that was being relayed off the scope, such as when it no longer passes
+    /// a filter, rather than leaving it frozen there until its track times out.
+    fn drop_track(&mut self, id: u32) {
+        if let Some(track) = self.tracks.remove(id) {
+            self.deregister(&track.callsign);
+            self.release(id, &track);
+        }
+    }
+
+    fn release(&mut self, id: u32, track: &Track) {
+        self.squawk_allocator.release(id);
+        self.correlator.release(id);
+        self.aircraft_info_provider.remove(&track.callsign);
+        self.frequency_provider.set(&track.callsign, None);
+        if self.sim_flight_plan_ids.remove(&id) {
+            self.sim_flight_plan_provider.remove(&track.callsign);
+        }
+    }
+
+    /// Fills the gaps between position updates with dead-reckoned positions, for the aircraft
+    /// relayed this cycle.
+    pub fn extrapolate(&self) {
+        let now = Instant::now();
+        let own_track = self.own_track.iter().filter(|_| !self.own_aircraft_hidden);
+        let relayed_tracks = self.tracks.iter().filter(|(id, _)| self.relayed_ids.contains(id)).map(|(_, track)| track);
+        for track in relayed_tracks.chain(own_track) {
+            if let Some(pos_rep) = extrapolated_position_update(track, now) {
+                self.msg_sender.send(pos_rep.to_string()).ok();
+            }
+        }
+    }
+
+    fn deregister(&self, callsign: &str) {
+        let dc = PilotDeregisterMessage::new(callsign, "1000000");
+        self.msg_sender.send(dc.to_string()).ok();
+        self.frequency_provider.set(callsign, None);
+        self.aircraft_info_provider.remove(callsign);
+    }
+
+    /// Works out both kinds of altitude on `qnh`, which is that of the nearest METAR, or failing
+    /// that the one the pilot has been given on the network. Without either, standard pressure is used.
+    fn sample(&self, aircraft: &SourceAircraft, qnh: Option<f64>) -> TrackSample {
+        let qnh = qnh.unwrap_or(STANDARD_PRESSURE_HPA);
+        let (true_alt, pressure_alt) = match aircraft.altitude {
+            SourceAltitude::True(true_alt) => (true_alt, altimetry::pressure_altitude(true_alt, qnh)),
+            SourceAltitude::Pressure(pressure_alt) => (altimetry::indicated_altitude(pressure_alt, qnh), pressure_alt),
+            SourceAltitude::Altimeter { pressure_alt, setting_hpa } => {
+                // Standard above the transition altitude says nothing about the pressure down below
+                let transition = self.preferences.transition();
+                let setting_is_qnh = !altimetry::is_standard_setting(setting_hpa) || !transition.is_above(pressure_alt, STANDARD_PRESSURE_HPA);
+                (altimetry::indicated_altitude(pressure_alt, if setting_is_qnh { setting_hpa } else { qnh }), pressure_alt)
+            },
+        };
+        TrackSample {
+            lat: aircraft.lat,
+            lon: aircraft.lon,
+            true_alt,
+            pressure_alt,
+            gs: aircraft.gs,
+            hdg: aircraft.hdg,
+            vs: aircraft.vs,
+            pitch: aircraft.pitch,
+            bank: aircraft.bank,
+            on_ground: aircraft.on_ground,
+            time: aircraft.time,
+        }
+    }
+}
+
+fn agl_alt(sample: &TrackSample, ground_alt: Option<f64>) -> Option<f64> {
+    if sample.on_ground { Some(0.0) } else { ground_alt.map(|ground_alt| sample.true_alt - ground_alt) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{self, Receiver};
+
+    use super::*;
+    use crate::core::config::Config;
+
+    #[derive(Clone)]
+    struct NoUi;
+
+    impl Ui for NoUi {
+        fn dispatch_message(&self, _message: Message) {}
+    }
+
+    fn relay(config: &str) -> (Relay<NoUi>, Receiver<String>) {
+        let mut preferences = Preferences::new(false, false, false, false);
+        preferences.load_config(&Config::parse(config));
+        let (msg_sender, msg_receiver) = mpsc::channel();
+        let shared = Shared {
+            preferences,
+            metar_provider: MetarProvider::new(),
+            vatsim_data_provider: VatsimDataProvider::new(),
+            airports: AirportDatabase::new(),
+            sim_flight_plan_provider: SimFlightPlanProvider::new(),
+            aircraft_info_provider: AircraftInfoProvider::new(),
+            frequency_provider: FrequencyProvider::new(),
+        };
+        let relay = Relay::new(shared, NoUi, msg_sender);
+        (relay, msg_receiver)
+    }
+
+    fn aircraft(callsign: &str, lat: f64, lon: f64, altitude: f64) -> SourceAircraft {
+        SourceAircraft {
+            id: 1,
+            callsign: callsign.to_owned(),
+            lat,
+            lon,
+            altitude: SourceAltitude::Pressure(altitude),
+            gs: 250.0,
+            hdg: 90.0,
+            vs: 0.0,
+            pitch: 0.0,
+            bank: 0.0,
+            on_ground: false,
+            ground_alt: None,
+            squawk: Some(4521),
+            transponder_mode: TransponderMode::ModeC,
+            frequency: None,
+            departure: None,
+            flight_plan: None,
+            info: None,
+            time: Instant::now(),
+        }
+    }
+
+    /// Relays `aircraft` on a cycle with a position update due, and returns what was sent.
+    fn relay_cycle(relay: &mut Relay<NoUi>, messages: &Receiver<String>, aircraft: SourceAircraft) -> Vec<String> {
+        let cycle = relay.begin_cycle(0, false);
+        relay.relay_traffic(&cycle, aircraft);
+        messages.try_iter().collect()
+    }
+
+    fn deregisters(messages: &[String], callsign: &str) -> bool {
+        messages.iter().any(|message| message.starts_with(&format!("#DP{}", callsign)))
+    }
+
+    #[test]
+    fn deregisters_aircraft_leaving_the_area() {
+        let (mut relay, messages) = relay("[area]\nmode = polygon\npolygon = 53 -7, 54 -7, 54 -6, 53 -6\n");
+        let sent = relay_cycle(&mut relay, &messages, aircraft("RYR12AB", 53.5, -6.5, 5000.0));
+        assert!(sent.iter().any(|message| message.starts_with("@N:RYR12AB:")));
+
+        let sent = relay_cycle(&mut relay, &messages, aircraft("RYR12AB", 55.0, -6.5, 5000.0));
+        assert!(deregisters(&sent, "RYR12AB"));
+        assert!(relay.tracks.get(1).is_none());
+
+        // Only the once, and not for aircraft that were never shown
+        assert!(relay_cycle(&mut relay, &messages, aircraft("RYR12AB", 55.0, -6.5, 5000.0)).is_empty());
+    }
+
+    #[test]
+    fn deregisters_aircraft_leaving_the_level_band() {
+        let (mut relay, messages) = relay("[filters]\nmax_altitude = FL100\n");
+        relay_cycle(&mut relay, &messages, aircraft("EIN123", 53.5, -6.5, 9000.0));
+        let sent = relay_cycle(&mut relay, &messages, aircraft("EIN123", 53.5, -6.5, 11000.0));
+        assert!(deregisters(&sent, "EIN123"));
+
+        // And back on the scope when it comes back down
+        let sent = relay_cycle(&mut relay, &messages, aircraft("EIN123", 53.5, -6.5, 9500.0));
+        assert!(sent.iter().any(|message| message.starts_with("@N:EIN123:")));
+    }
+
+    #[test]
+    fn deregisters_aircraft_the_callsign_filter_now_hides() {
+        let (mut relay, messages) = relay("[filters]\nexclude = ASXGS*\n");
+        relay_cycle(&mut relay, &messages, aircraft("ASXGS12", 53.5, -6.5, 0.0));
+        assert!(relay.tracks.get(1).is_none());
+
+        relay_cycle(&mut relay, &messages, aircraft("EIN123", 53.5, -6.5, 5000.0));
+        relay.preferences.load_config(&Config::parse("[filters]\nexclude = EIN*\n"));
+        let sent = relay_cycle(&mut relay, &messages, aircraft("EIN123", 53.5, -6.5, 5000.0));
+        assert!(deregisters(&sent, "EIN123"));
+    }
+
+    #[test]
+    fn deregisters_aircraft_tuning_away_from_the_frequency() {
+        let (mut relay, messages) = relay("[filters]\nfrequency = 118.100\n");
+        let on_tower = SourceAircraft { frequency: "118.100".parse().ok(), ..aircraft("EIN123", 53.5, -6.5, 2000.0) };
+        relay_cycle(&mut relay, &messages, on_tower);
+        let on_approach = SourceAircraft { frequency: "121.100".parse().ok(), ..aircraft("EIN123", 53.5, -6.5, 2000.0) };
+        let sent = relay_cycle(&mut relay, &messages, on_approach);
+        assert!(deregisters(&sent, "EIN123"));
+    }
+
+    #[test]
+    fn deregisters_aircraft_no_longer_heard_from() {
+        let (mut relay, messages) = relay("");
+        let heard_long_ago = SourceAircraft { time: Instant::now() - std::time::Duration::from_secs(60), ..aircraft("EIN123", 53.5, -6.5, 5000.0) };
+        relay_cycle(&mut relay, &messages, heard_long_ago);
+        assert_eq!(relay.prune(), vec![1]);
+        assert!(deregisters(&messages.try_iter().collect::<Vec<_>>(), "EIN123"));
+        assert!(relay.prune().is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashMap, io::{self, Read}, net::{TcpStream, ToSocketAddrs}, time::{Duration, Instant}};
+
+use fsd_interface::TransponderMode;
+
+use super::{SourceAircraft, SourceAltitude, SourceError, TrafficSource};
+
+pub const DEFAULT_SBS_ADDRESS: &str = "127.0.0.1:30003";
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Aircraft not heard from for this long have gone out of range.
+const AIRCRAFT_TIMEOUT: Duration = Duration::from_secs(60);
+
+
+
+/// ADS-B traffic from a BaseStation (SBS-1) feed, as served by dump1090 or readsb on port 30003.
+///
+/// Each line is one message about one aircraft, such as `MSG,3,...` with its position, and only
+/// fills in some of the fields, so aircraft are pieced together from the lines as they come.
+pub struct SbsSource {
+    address: String,
+    stream: Option<TcpStream>,
+    /// Anything after the last complete line read.
+    partial_line: String,
+    aircraft: HashMap<u32, SbsAircraft>,
+}
+
+#[derive(Debug, Default)]
+struct SbsAircraft {
+    callsign: Option<String>,
+    /// Barometric, as broadcast.
+    altitude: Option<f64>,
+    gs: Option<f64>,
+    track: Option<f64>,
+    vs: Option<f64>,
+    position: Option<(f64, f64)>,
+    squawk: Option<u16>,
+    on_ground: bool,
+    /// When the position was last updated.
+    position_time: Option<Instant>,
+    last_heard: Option<Instant>,
+}
+
+impl SbsSource {
+    pub fn new(address: String) -> SbsSource {
+        SbsSource { address, stream: None, partial_line: String::new(), aircraft: HashMap::new() }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let address = self.address.to_socket_addrs()?.next().ok_or(io::ErrorKind::AddrNotAvailable)?;
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_nonblocking(true)?;
+        Ok(stream)
+    }
+
+    /// Reads whatever has arrived since the last poll, without waiting for more.
+    fn read_available(&mut self) -> Result<(), SourceError> {
+        // Taken out so that a broken connection is dropped and made again next time
+        let mut stream = match self.stream.take() {
+            Some(stream) => stream,
+            None => self.connect()?,
+        };
+        let mut received = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return Err(SourceError::NoData),
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.stream = Some(stream);
+
+        self.partial_line.push_str(&String::from_utf8_lossy(&received));
+        let complete = match self.partial_line.rfind('\n') {
+            Some(end) => self.partial_line.drain(..=end).collect::<String>(),
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        for line in complete.lines() {
+            self.handle_line(line, now);
+        }
+        Ok(())
+    }
+
+    fn handle_line(&mut self, line: &str, now: Instant) {
+        let fields: Vec<&str> = line.trim().split(',').map(|field| field.trim()).collect();
+        if fields.len() < 22 || fields[0] != "MSG" { return }
+        let id = match u32::from_str_radix(fields[4], 16) {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        let aircraft = self.aircraft.entry(id).or_default();
+        aircraft.last_heard = Some(now);
+
+        // Which fields are filled in depends on the message type, so take whatever is there
+        if !fields[10].is_empty() {
+            aircraft.callsign = Some(fields[10].to_uppercase());
+        }
+        if let Ok(altitude) = fields[11].parse() {
+            aircraft.altitude = Some(altitude);
+        }
+        if let Ok(gs) = fields[12].parse() {
+            aircraft.gs = Some(gs);
+        }
+        if let Ok(track) = fields[13].parse() {
+            aircraft.track = Some(track);
+        }
+        if let (Ok(lat), Ok(lon)) = (fields[14].parse(), fields[15].parse()) {
+            aircraft.position = Some((lat, lon));
+            aircraft.position_time = Some(now);
+        }
+        if let Ok(vs) = fields[16].parse() {
+            aircraft.vs = Some(vs);
+        }
+        if fields[17].len() == 4 {
+            if let Ok(squawk) = fields[17].parse() {
+                aircraft.squawk = Some(squawk);
+            }
+        }
+        if !fields[21].is_empty() {
+            aircraft.on_ground = fields[21] != "0";
+        }
+    }
+
+    /// Everything heard about recently that can be shown. Nothing is sent until we know who an
+    /// aircraft is, where it is and how high it is.
+    fn aircraft_list(&mut self) -> Vec<SourceAircraft> {
+        self.aircraft.retain(|_, aircraft| aircraft.last_heard.is_some_and(|last_heard| last_heard.elapsed() < AIRCRAFT_TIMEOUT));
+        self.aircraft.iter().filter_map(|(&id, aircraft)| {
+            let (lat, lon) = aircraft.position?;
+            Some(SourceAircraft {
+                id,
+                callsign: aircraft.callsign.clone()?,
+                lat,
+                lon,
+                altitude: SourceAltitude::Pressure(aircraft.altitude?),
+                gs: aircraft.gs.unwrap_or(0.0),
+                hdg: aircraft.track.unwrap_or(0.0),
+                vs: aircraft.vs.unwrap_or(0.0),
+                pitch: 0.0,
+                bank: 0.0,
+                on_ground: aircraft.on_ground,
+                squawk: aircraft.squawk,
+                ground_alt: None,
+                transponder_mode: TransponderMode::ModeC,
+                frequency: None,
+                departure: None,
+                flight_plan: None,
+                info: None,
+                time: aircraft.position_time?,
+            })
+        }).collect()
+    }
+}
+
+impl TrafficSource for SbsSource {
+    fn name(&self) -> String {
+        format!("SBS feed at {}", self.address)
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        self.read_available()?;
+        Ok(self.aircraft_list())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpListener, thread};
+
+    use super::*;
+
+    const IDENTIFICATION: &str = "MSG,1,1,1,4CA123,1,2024/05/01,12:00:00.000,2024/05/01,12:00:00.000,RYR12AB ,,,,,,,,,,,0";
+    const POSITION: &str = "MSG,3,1,1,4CA123,1,2024/05/01,12:00:01.000,2024/05/01,12:00:01.000,,35000,,,53.35012,-6.20054,,,0,0,0,0";
+    const VELOCITY: &str = "MSG,4,1,1,4CA123,1,2024/05/01,12:00:02.000,2024/05/01,12:00:02.000,,,450,270.5,,,-64,,0,0,0,0";
+    const SQUAWK: &str = "MSG,6,1,1,4CA123,1,2024/05/01,12:00:03.000,2024/05/01,12:00:03.000,,,,,,,,4521,0,0,0,0";
+
+    #[test]
+    fn pieces_aircraft_together_from_messages() {
+        let mut source = SbsSource::new(String::new());
+        let now = Instant::now();
+        for line in [IDENTIFICATION, POSITION, VELOCITY, SQUAWK] {
+            source.handle_line(line, now);
+        }
+        let aircraft_list = source.aircraft_list();
+        assert_eq!(aircraft_list.len(), 1);
+        let aircraft = &aircraft_list[0];
+        assert_eq!(aircraft.id, 0x4CA123);
+        assert_eq!(aircraft.callsign, "RYR12AB");
+        assert_eq!((aircraft.lat, aircraft.lon), (53.35012, -6.20054));
+        assert!(matches!(aircraft.altitude, SourceAltitude::Pressure(altitude) if altitude == 35000.0));
+        assert_eq!((aircraft.gs, aircraft.hdg, aircraft.vs), (450.0, 270.5, -64.0));
+        assert_eq!(aircraft.squawk, Some(4521));
+        assert!(!aircraft.on_ground);
+        assert_eq!(aircraft.time, now);
+    }
+
+    #[test]
+    fn waits_for_identity_position_and_altitude() {
+        let mut source = SbsSource::new(String::new());
+        let now = Instant::now();
+        source.handle_line(POSITION, now);
+        assert!(source.aircraft_list().is_empty());
+
+        // A position without an altitude isn't enough either
+        let mut source = SbsSource::new(String::new());
+        source.handle_line(IDENTIFICATION, now);
+        source.handle_line("MSG,3,1,1,4CA123,1,,,,,,,,,53.35012,-6.20054,,,0,0,0,0", now);
+        assert!(source.aircraft_list().is_empty());
+        source.handle_line(POSITION, now);
+        assert_eq!(source.aircraft_list().len(), 1);
+
+        // Nor is anything that isn't a well-formed message
+        source.handle_line("MSG,3,1,1,NOTHEX,1,,,,,,35000,,,1.0,1.0,,,0,0,0,0", now);
+        source.handle_line("STA,,1,1,4CA124,1,,,,,RMV", now);
+        assert_eq!(source.aircraft_list().len(), 1);
+    }
+
+    #[test]
+    fn polls_lines_from_the_feed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut source = SbsSource::new(listener.local_addr().unwrap().to_string());
+        let feed = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            // Split mid-line, as TCP is free to
+            let lines = format!("{}\r\n{}\r\n{}\r\n", IDENTIFICATION, POSITION, VELOCITY);
+            let (first, second) = lines.split_at(100);
+            stream.write_all(first.as_bytes()).unwrap();
+            stream.flush().unwrap();
+            thread::sleep(Duration::from_millis(50));
+            stream.write_all(second.as_bytes()).unwrap();
+            thread::sleep(Duration::from_millis(500));
+        });
+
+        let started = Instant::now();
+        let mut aircraft_list = Vec::new();
+        while aircraft_list.is_empty() && started.elapsed() < Duration::from_secs(5) {
+            aircraft_list = source.poll().unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(aircraft_list.len(), 1);
+        assert_eq!(aircraft_list[0].callsign, "RYR12AB");
+        assert_eq!(aircraft_list[0].gs, 450.0);
+        feed.join().unwrap();
+    }
+}
@@ -0,0 +1,61 @@
+use fsd_interface::TransponderMode;
+
+use super::{on_ground_near_field, SourceAircraft, SourceAltitude, SourceError, TrafficSource};
+use crate::core::{airports::AirportDatabase, vatsim::{Details, VatsimDataProvider}};
+
+
+
+/// Network traffic straight from the VATSIM data feed, for watching without a sim. Positions
+/// only change when the feed is refreshed, every 15 seconds.
+pub struct VatsimSource {
+    vatsim_data_provider: VatsimDataProvider,
+    /// The feed doesn't say whether a pilot is on the ground, so it is worked out from the field elevation.
+    airports: AirportDatabase,
+}
+
+impl VatsimSource {
+    pub fn new(vatsim_data_provider: VatsimDataProvider, airports: AirportDatabase) -> VatsimSource {
+        VatsimSource { vatsim_data_provider, airports }
+    }
+}
+
+impl TrafficSource for VatsimSource {
+    fn name(&self) -> String {
+        String::from("VATSIM data feed")
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        if !self.vatsim_data_provider.last_update_successful() { return Err(SourceError::NoData) }
+        let time = self.vatsim_data_provider.last_update_time().ok_or(SourceError::NoData)?;
+        Ok(self.vatsim_data_provider.all_aircraft().into_iter().map(|details| aircraft_from_details(details, &self.airports, time)).collect())
+    }
+
+    fn needs_vatsim_data(&self) -> bool {
+        true
+    }
+}
+
+fn aircraft_from_details(details: Details, airports: &AirportDatabase, time: std::time::Instant) -> SourceAircraft {
+    let on_ground = on_ground_near_field(airports, details.latitude, details.longitude, details.altitude as f64, details.groundspeed as f64);
+    SourceAircraft {
+        id: details.cid as u32,
+        squawk: details.transponder.parse().ok(),
+        callsign: details.callsign,
+        lat: details.latitude,
+        lon: details.longitude,
+        altitude: SourceAltitude::True(details.altitude as f64),
+        gs: details.groundspeed as f64,
+        hdg: details.heading as f64,
+        vs: 0.0,
+        pitch: 0.0,
+        bank: 0.0,
+        on_ground,
+        ground_alt: None,
+        transponder_mode: TransponderMode::ModeC,
+        frequency: None,
+        departure: None,
+        flight_plan: None,
+        info: None,
+        time,
+    }
+}
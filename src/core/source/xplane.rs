@@ -0,0 +1,411 @@
+use std::{collections::HashMap, io, net::{ToSocketAddrs, UdpSocket}, time::{Duration, Instant}};
+
+use fsd_interface::TransponderMode;
+
+use super::{on_ground_near_field, SourceAircraft, SourceAltitude, SourceError, TrafficSource};
+use crate::core::{airports::AirportDatabase, altimetry};
+
+/// Where X-Plane's Data Output is sent, set under "Send network data output".
+pub const DEFAULT_XPLANE_LISTEN_PORT: u16 = 49005;
+/// X-Plane's own UDP port, where dataref subscriptions go.
+pub const DEFAULT_XPLANE_ADDRESS: &str = "127.0.0.1:49000";
+/// TCAS slots asked for. Slot 0 is our own aircraft.
+const MAX_TARGETS: usize = 20;
+const SUBSCRIPTION_FREQUENCY_HZ: i32 = 2;
+/// Subscriptions are sent again if nothing comes back for this long, in case X-Plane was restarted.
+const RESUBSCRIBE_INTERVAL: Duration = Duration::from_secs(10);
+const NO_DATA_TIMEOUT: Duration = Duration::from_secs(5);
+const ON_GROUND_MAX_AGL_FT: f64 = 5.0;
+const KNOTS_PER_METRE_PER_SECOND: f64 = 1.94384;
+
+/// Data Output groups used for our own aircraft.
+const DATA_SPEEDS: i32 = 3;
+const DATA_MACH_VVI_G: i32 = 4;
+const DATA_PITCH_ROLL_HEADINGS: i32 = 17;
+const DATA_LAT_LON_ALT: i32 = 20;
+
+/// Dataref for each TCAS target, subscribed to per slot. Ids are `slot * REF_STRIDE + index`.
+const TARGET_DATAREFS: [&str; 11] = [
+    "sim/cockpit2/tcas/targets/position/lat",
+    "sim/cockpit2/tcas/targets/position/lon",
+    "sim/cockpit2/tcas/targets/position/ele",
+    "sim/cockpit2/tcas/targets/position/psi",
+    "sim/cockpit2/tcas/targets/position/the",
+    "sim/cockpit2/tcas/targets/position/phi",
+    "sim/cockpit2/tcas/targets/position/vx",
+    "sim/cockpit2/tcas/targets/position/vy",
+    "sim/cockpit2/tcas/targets/position/vz",
+    "sim/cockpit2/tcas/targets/modeC_code",
+    "sim/cockpit2/tcas/targets/modeS_id",
+];
+const LAT: usize = 0;
+const LON: usize = 1;
+const ELE: usize = 2;
+const PSI: usize = 3;
+const THE: usize = 4;
+const PHI: usize = 5;
+const VX: usize = 6;
+const VY: usize = 7;
+const VZ: usize = 8;
+const MODE_C_CODE: usize = 9;
+const MODE_S_ID: usize = 10;
+/// The flight id is a byte array, 8 per target, so each byte is a dataref of its own.
+const FLIGHT_ID_LENGTH: usize = 8;
+const REF_STRIDE: usize = 32;
+const OWN_SQUAWK_REF: i32 = 0;
+const OWN_SQUAWK_DATAREF: &str = "sim/cockpit/radios/transponder_code";
+
+
+
+/// X-Plane, over its UDP interface. Our own aircraft comes from the Data Output groups for
+/// speeds (3), Mach/VVI (4), pitch/roll/headings (17) and lat/lon/altitude (20), which have to
+/// be ticked for network output. Other traffic comes from the TCAS datarefs, which are filled
+/// in for AI and for multiplayer clients that feed them, and which we subscribe to ourselves.
+pub struct XPlaneSource {
+    port: u16,
+    xplane_address: String,
+    socket: Option<UdpSocket>,
+    own: OwnShip,
+    targets: HashMap<usize, Target>,
+    last_received: Option<Instant>,
+    last_reference_received: Option<Instant>,
+    last_subscribed: Option<Instant>,
+    /// TCAS targets don't say whether they are on the ground, so it is worked out from the field elevation.
+    airports: AirportDatabase,
+}
+
+#[derive(Debug, Default)]
+struct OwnShip {
+    lat: f64,
+    lon: f64,
+    alt: f64,
+    agl: f64,
+    pitch: f64,
+    bank: f64,
+    hdg: f64,
+    gs: f64,
+    vs: f64,
+    squawk: Option<u16>,
+    time: Option<Instant>,
+}
+
+#[derive(Debug, Default)]
+struct Target {
+    values: [f32; TARGET_DATAREFS.len()],
+    flight_id: [u8; FLIGHT_ID_LENGTH],
+    time: Option<Instant>,
+}
+
+impl XPlaneSource {
+    pub fn new(port: u16, xplane_address: String, airports: AirportDatabase) -> XPlaneSource {
+        XPlaneSource {
+            port,
+            xplane_address,
+            socket: None,
+            own: OwnShip::default(),
+            targets: HashMap::new(),
+            last_received: None,
+            last_reference_received: None,
+            last_subscribed: None,
+            airports,
+        }
+    }
+
+    fn socket(&mut self) -> io::Result<&UdpSocket> {
+        if self.socket.is_none() {
+            let socket = UdpSocket::bind(("0.0.0.0", self.port))?;
+            socket.set_nonblocking(true)?;
+            self.socket = Some(socket);
+        }
+        Ok(self.socket.as_ref().unwrap())
+    }
+
+    /// Every dataref we want, with the id it comes back under.
+    fn references() -> Vec<(i32, String)> {
+        let mut references = vec![(OWN_SQUAWK_REF, String::from(OWN_SQUAWK_DATAREF))];
+        for slot in 1..MAX_TARGETS {
+            for (index, dataref) in TARGET_DATAREFS.iter().enumerate() {
+                references.push(((slot * REF_STRIDE + index) as i32, format!("{}[{}]", dataref, slot)));
+            }
+            for byte in 0..FLIGHT_ID_LENGTH {
+                let index = TARGET_DATAREFS.len() + byte;
+                references.push(((slot * REF_STRIDE + index) as i32, format!("sim/cockpit2/tcas/targets/flight_id[{}]", slot * FLIGHT_ID_LENGTH + byte)));
+            }
+        }
+        references
+    }
+
+    /// Asks X-Plane to send each dataref `frequency` times a second, or to stop if it is 0.
+    fn subscribe(&mut self, frequency: i32) -> io::Result<()> {
+        let address = self.xplane_address.to_socket_addrs()?.next().ok_or(io::ErrorKind::AddrNotAvailable)?;
+        let socket = self.socket()?;
+        for (id, dataref) in XPlaneSource::references() {
+            socket.send_to(&rref_request(frequency, id, &dataref), address)?;
+        }
+        Ok(())
+    }
+
+    fn receive_available(&mut self) -> io::Result<()> {
+        let mut buf = [0; 2048];
+        loop {
+            let len = match self.socket()?.recv_from(&mut buf) {
+                Ok((len, _)) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                // Windows reports an earlier send to a closed port this way
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            };
+            let now = Instant::now();
+            if self.handle_packet(&buf[..len], now) {
+                self.last_received = Some(now);
+            }
+        }
+    }
+
+    /// Returns whether the packet was one of ours.
+    fn handle_packet(&mut self, packet: &[u8], now: Instant) -> bool {
+        if packet.len() < 5 { return false }
+        match &packet[..4] {
+            b"DATA" => self.handle_data(&packet[5..], now),
+            b"RREF" => self.handle_references(&packet[5..], now),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Data Output records are an index followed by eight values.
+    fn handle_data(&mut self, records: &[u8], now: Instant) {
+        for record in records.chunks_exact(36) {
+            let index = i32::from_le_bytes(record[..4].try_into().unwrap());
+            let values: Vec<f64> = record[4..].chunks_exact(4).map(|value| f32::from_le_bytes(value.try_into().unwrap()) as f64).collect();
+            match index {
+                DATA_SPEEDS => self.own.gs = values[3],
+                DATA_MACH_VVI_G => self.own.vs = values[2],
+                DATA_PITCH_ROLL_HEADINGS => (self.own.pitch, self.own.bank, self.own.hdg) = (values[0], values[1], values[2]),
+                DATA_LAT_LON_ALT => {
+                    (self.own.lat, self.own.lon, self.own.alt, self.own.agl) = (values[0], values[1], values[2], values[3]);
+                    self.own.time = Some(now);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    /// Subscribed datarefs come back as pairs of id and value.
+    fn handle_references(&mut self, pairs: &[u8], now: Instant) {
+        for pair in pairs.chunks_exact(8) {
+            let id = i32::from_le_bytes(pair[..4].try_into().unwrap());
+            let value = f32::from_le_bytes(pair[4..].try_into().unwrap());
+            if id == OWN_SQUAWK_REF {
+                self.own.squawk = Some(value as u16);
+                continue;
+            }
+            let (slot, index) = (id as usize / REF_STRIDE, id as usize % REF_STRIDE);
+            let target = self.targets.entry(slot).or_default();
+            if index < TARGET_DATAREFS.len() {
+                target.values[index] = value;
+            } else if index < TARGET_DATAREFS.len() + FLIGHT_ID_LENGTH {
+                target.flight_id[index - TARGET_DATAREFS.len()] = value as u8;
+            }
+            target.time = Some(now);
+        }
+        self.last_reference_received = Some(now);
+    }
+
+    /// The TCAS targets in occupied slots.
+    fn aircraft_list(&self) -> Vec<SourceAircraft> {
+        self.targets.iter().filter_map(|(&slot, target)| {
+            let value = |index: usize| target.values[index] as f64;
+            // Empty slots are all zeros
+            if value(LAT) == 0.0 && value(LON) == 0.0 { return None }
+            let mode_s = value(MODE_S_ID) as u32;
+            let flight_id = String::from_utf8_lossy(&target.flight_id).trim_matches(|c: char| c == '\0' || c.is_whitespace()).to_uppercase();
+            let gs = value(VX).hypot(value(VZ)) * KNOTS_PER_METRE_PER_SECOND;
+            let alt = altimetry::metres_to_feet(value(ELE));
+            Some(SourceAircraft {
+                id: if mode_s != 0 { mode_s } else { slot as u32 },
+                callsign: if flight_id.is_empty() { format!("XP{}", slot) } else { flight_id },
+                lat: value(LAT),
+                lon: value(LON),
+                altitude: SourceAltitude::True(alt),
+                gs,
+                hdg: value(PSI),
+                vs: altimetry::metres_to_feet(value(VY)) * 60.0,
+                pitch: value(THE),
+                bank: value(PHI),
+                on_ground: on_ground_near_field(&self.airports, value(LAT), value(LON), alt, gs),
+                squawk: Some(value(MODE_C_CODE) as u16).filter(|squawk| *squawk > 0),
+                ground_alt: None,
+                transponder_mode: TransponderMode::ModeC,
+                frequency: None,
+                departure: None,
+                flight_plan: None,
+                info: None,
+                time: target.time?,
+            })
+        }).collect()
+    }
+}
+
+impl TrafficSource for XPlaneSource {
+    fn name(&self) -> String {
+        format!("X-Plane on port {}", self.port)
+    }
+
+    fn poll(&mut self) -> Result<Vec<SourceAircraft>, SourceError> {
+        let references_stale = self.last_reference_received.is_none_or(|last| last.elapsed() > NO_DATA_TIMEOUT);
+        let resubscribe_due = self.last_subscribed.is_none_or(|last| last.elapsed() > RESUBSCRIBE_INTERVAL);
+        if references_stale && resubscribe_due {
+            self.subscribe(SUBSCRIPTION_FREQUENCY_HZ)?;
+            self.last_subscribed = Some(Instant::now());
+        }
+        self.receive_available()?;
+        if self.last_received.is_none_or(|last| last.elapsed() > NO_DATA_TIMEOUT) {
+            return Err(SourceError::NoData);
+        }
+
+        Ok(self.aircraft_list())
+    }
+
+    fn own_aircraft(&self) -> Option<SourceAircraft> {
+        let own = &self.own;
+        Some(SourceAircraft {
+            id: 0,
+            callsign: String::new(),
+            lat: own.lat,
+            lon: own.lon,
+            altitude: SourceAltitude::True(own.alt),
+            gs: own.gs,
+            hdg: own.hdg,
+            vs: own.vs,
+            pitch: own.pitch,
+            bank: own.bank,
+            on_ground: own.agl < ON_GROUND_MAX_AGL_FT,
+            squawk: own.squawk,
+            ground_alt: Some(own.alt - own.agl),
+            transponder_mode: TransponderMode::ModeC,
+            frequency: None,
+            departure: None,
+            flight_plan: None,
+            info: None,
+            time: own.time?,
+        })
+    }
+}
+
+impl Drop for XPlaneSource {
+    fn drop(&mut self) {
+        if self.last_subscribed.is_some() {
+            self.subscribe(0).ok();
+        }
+    }
+}
+
+/// A dataref subscription: the header, frequency, id, and the dataref padded to 400 bytes.
+fn rref_request(frequency: i32, id: i32, dataref: &str) -> Vec<u8> {
+    let mut request = Vec::with_capacity(413);
+    request.extend_from_slice(b"RREF\0");
+    request.extend_from_slice(&frequency.to_le_bytes());
+    request.extend_from_slice(&id.to_le_bytes());
+    let mut path = [0; 400];
+    let len = dataref.len().min(path.len() - 1);
+    path[..len].copy_from_slice(&dataref.as_bytes()[..len]);
+    request.extend_from_slice(&path);
+    request
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Data Output groups 3, 4, 17 and 20 for our own aircraft, plus group 13 which we don't use.
+    const DATA_CAPTURE: &[u8] = include_bytes!("testdata/xplane_data.bin");
+    /// Our squawk, a TCAS target in slot 1 flying as BAW12, and an empty slot 2.
+    const RREF_CAPTURE: &[u8] = include_bytes!("testdata/xplane_rref.bin");
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-3, "{} is not {}", actual, expected);
+    }
+
+    #[test]
+    fn reads_own_aircraft_from_data_output() {
+        let mut source = XPlaneSource::new(0, String::new(), AirportDatabase::new());
+        let now = Instant::now();
+        assert!(source.own_aircraft().is_none());
+        assert!(source.handle_packet(DATA_CAPTURE, now));
+        assert!(source.handle_packet(RREF_CAPTURE, now));
+
+        let own = source.own_aircraft().unwrap();
+        assert_close(own.lat, 51.4775);
+        assert_close(own.lon, -0.4614);
+        assert!(matches!(own.altitude, SourceAltitude::True(altitude) if altitude == 3500.0));
+        assert_eq!(own.ground_alt, Some(83.0));
+        assert!(!own.on_ground);
+        assert_eq!((own.gs, own.vs), (245.5, -700.0));
+        assert_eq!((own.pitch, own.bank, own.hdg), (2.5, -10.0, 271.25));
+        assert_eq!(own.squawk, Some(4521));
+        assert_eq!(own.time, now);
+    }
+
+    #[test]
+    fn reads_tcas_targets_from_references() {
+        let mut source = XPlaneSource::new(0, String::new(), AirportDatabase::new());
+        let now = Instant::now();
+        source.handle_references(&RREF_CAPTURE[5..], now);
+
+        let aircraft_list = source.aircraft_list();
+        assert_eq!(aircraft_list.len(), 1);
+        let aircraft = &aircraft_list[0];
+        assert_eq!(aircraft.id, 0x400ABC);
+        assert_eq!(aircraft.callsign, "BAW12");
+        assert_close(aircraft.lat, 53.4213);
+        assert_close(aircraft.lon, -6.2701);
+        assert!(matches!(aircraft.altitude, SourceAltitude::True(altitude) if (altitude - 3280.84).abs() < 0.01));
+        assert_eq!((aircraft.hdg, aircraft.pitch, aircraft.bank), (90.0, 1.5, -2.0));
+        assert_close(aircraft.gs, 194.384);
+        assert_close(aircraft.vs, 984.252);
+        assert!(!aircraft.on_ground);
+        assert_eq!(aircraft.squawk, Some(7000));
+        assert_eq!(source.last_reference_received, Some(now));
+
+        // Without a flight id or Mode S id the slot stands in for both
+        let cleared: Vec<u8> = (MODE_S_ID..TARGET_DATAREFS.len() + FLIGHT_ID_LENGTH)
+            .flat_map(|index| [((REF_STRIDE + index) as i32).to_le_bytes(), 0f32.to_le_bytes()].concat())
+            .collect();
+        source.handle_references(&cleared, now);
+        let aircraft = &source.aircraft_list()[0];
+        assert_eq!((aircraft.id, aircraft.callsign.as_str()), (1, "XP1"));
+    }
+
+    #[test]
+    fn ignores_other_packets() {
+        let mut source = XPlaneSource::new(0, String::new(), AirportDatabase::new());
+        let now = Instant::now();
+        assert!(!source.handle_packet(b"BECN\0\x01\x01", now));
+        assert!(!source.handle_packet(b"DATA", now));
+        // Half a record is dropped rather than misread
+        assert!(source.handle_packet(&DATA_CAPTURE[..5 + 18], now));
+        assert!(source.own_aircraft().is_none());
+    }
+
+    #[test]
+    fn builds_subscription_requests() {
+        let request = rref_request(2, 45, "sim/cockpit2/tcas/targets/position/lat[1]");
+        assert_eq!(request.len(), 413);
+        assert_eq!(&request[..5], b"RREF\0");
+        assert_eq!(i32::from_le_bytes(request[5..9].try_into().unwrap()), 2);
+        assert_eq!(i32::from_le_bytes(request[9..13].try_into().unwrap()), 45);
+        assert_eq!(&request[13..54], b"sim/cockpit2/tcas/targets/position/lat[1]");
+        assert!(request[54..].iter().all(|&b| b == 0));
+
+        // The path is cut short to leave room for its terminator
+        let request = rref_request(0, 0, &"x".repeat(500));
+        assert_eq!(request.len(), 413);
+        assert_eq!(request[412], 0);
+
+        let references = XPlaneSource::references();
+        assert_eq!(references[0], (OWN_SQUAWK_REF, String::from(OWN_SQUAWK_DATAREF)));
+        assert!(references.contains(&((REF_STRIDE + LAT) as i32, String::from("sim/cockpit2/tcas/targets/position/lat[1]"))));
+        assert!(references.contains(&((2 * REF_STRIDE + TARGET_DATAREFS.len() + 1) as i32, String::from("sim/cockpit2/tcas/targets/flight_id[17]"))));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{cmp::Reverse, collections::{HashMap, HashSet}};
+
+use super::config::Config;
+
+pub const CONSPICUITY_CODE: u16 = 2000;
+/// Codes never handed out: emergencies, conspicuity and the like.
+const RESERVED_CODES: [u16; 8] = [0, 1000, 1200, 2000, 2200, 7000, 7500, 7600];
+const EMERGENCY_CODE: u16 = 7700;
+const DEFAULT_POOL: &str = "0101-0177, 0201-0277, 0301-0377, 0401-0477, 0501-0577, 0601-0677, 0701-0777";
+
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocationMode {
+    /// Everything squawks the conspicuity code, as before.
+    Off,
+    /// Codes are taken in order from one shared pool.
+    Sequential,
+    /// Codes are taken from the range for the aircraft's origin, falling back to the shared pool.
+    Orcam,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SquawkSettings {
+    pub mode: AllocationMode,
+    pub pool: Vec<u16>,
+    /// Origin ICAO prefixes and their code ranges, longest prefix first.
+    pub orcam_ranges: Vec<(String, Vec<u16>)>,
+}
+
+impl SquawkSettings {
+    pub fn from_config(config: &Config) -> SquawkSettings {
+        let mode = match config.get("squawks", "mode").map(|mode| mode.to_lowercase()).as_deref() {
+            Some("off") => AllocationMode::Off,
+            Some("orcam") => AllocationMode::Orcam,
+            _ => AllocationMode::Sequential,
+        };
+        let pool = parse_code_ranges(config.get("squawks", "pool").unwrap_or(DEFAULT_POOL));
+        let mut orcam_ranges: Vec<(String, Vec<u16>)> = config.section("squawk_ranges").iter().map(|(prefix, ranges)| (prefix.to_uppercase(), parse_code_ranges(ranges))).collect();
+        orcam_ranges.sort_by_key(|(prefix, _)| Reverse(prefix.len()));
+        SquawkSettings { mode, pool, orcam_ranges }
+    }
+}
+
+impl Default for SquawkSettings {
+    fn default() -> SquawkSettings {
+        SquawkSettings::from_config(&Config::default())
+    }
+}
+
+
+/// Hands out a stable, unique squawk to each tracked aircraft for as long as it is tracked.
+pub struct SquawkAllocator {
+    settings: SquawkSettings,
+    assigned: HashMap<u32, u16>,
+    in_use: HashSet<u16>,
+    /// Where to resume searching each list of codes, keyed by origin prefix ("" for the pool).
+    cursors: HashMap<String, usize>,
+}
+
+impl SquawkAllocator {
+    pub fn new(settings: SquawkSettings) -> SquawkAllocator {
+        SquawkAllocator { settings, assigned: HashMap::new(), in_use: HashSet::new(), cursors: HashMap::new() }
+    }
+
+    /// Switches to new settings if they have changed. Codes handed out under the old ones may
+    /// not be in the new ranges, so every aircraft is given a new code.
+    pub fn update_settings(&mut self, settings: SquawkSettings) {
+        if settings != self.settings {
+            *self = SquawkAllocator::new(settings);
+        }
+    }
+
+    /// The code for a track, allocating one the first time the track is seen.
+    pub fn assign(&mut self, id: u32, origin: Option<&str>) -> u16 {
+        if self.settings.mode == AllocationMode::Off { return CONSPICUITY_CODE; }
+        if let Some(code) = self.assigned.get(&id) {
+            return *code;
+        }
+
+        let orcam_range = match (self.settings.mode, origin) {
+            (AllocationMode::Orcam, Some(origin)) => self.settings.orcam_ranges.iter().find(|(prefix, _)| origin.starts_with(prefix.as_str())).cloned(),
+            _ => None,
+        };
+        let code = orcam_range.and_then(|(prefix, codes)| self.take_from(&prefix, &codes))
+            .or_else(|| self.take_from("", &self.settings.pool.clone()))
+            .unwrap_or(CONSPICUITY_CODE);
+        if code != CONSPICUITY_CODE {
+            self.assigned.insert(id, code);
+            self.in_use.insert(code);
+        }
+        code
+    }
+
+    pub fn release(&mut self, id: u32) {
+        if let Some(code) = self.assigned.remove(&id) {
+            self.in_use.remove(&code);
+        }
+    }
+
+    fn take_from(&mut self, key: &str, codes: &[u16]) -> Option<u16> {
+        if codes.is_empty() { return None; }
+        let cursor = self.cursors.get(key).copied().unwrap_or(0);
+        let found = (0..codes.len()).map(|i| (cursor + i) % codes.len()).find(|i| !self.in_use.contains(&codes[*i]))?;
+        self.cursors.insert(key.to_owned(), found + 1);
+        Some(codes[found])
+    }
+}
+
+
+/// Parses a list such as `4201-4277, 5301` into the valid codes it covers.
+fn parse_code_ranges(ranges: &str) -> Vec<u16> {
+    ranges.split(',').filter_map(|range| {
+        let range = range.trim();
+        let (start, end) = range.split_once('-').unwrap_or((range, range));
+        Some((start.trim().parse::<u16>().ok()?, end.trim().parse::<u16>().ok()?))
+    }).flat_map(|(start, end)| start..=end).filter(|code| is_assignable(*code)).collect()
+}
+
+fn is_assignable(code: u16) -> bool {
+    let octal_digits = code <= 7777 && [code / 1000, code / 100 % 10, code / 10 % 10, code % 10].iter().all(|digit| *digit <= 7);
+    octal_digits && code != EMERGENCY_CODE && !RESERVED_CODES.contains(&code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(text: &str) -> SquawkSettings {
+        SquawkSettings::from_config(&Config::parse(text))
+    }
+
+    #[test]
+    fn codes_are_stable_and_unique() {
+        let mut allocator = SquawkAllocator::new(settings("[squawks]\npool = 4201-4203\n"));
+        assert_eq!(allocator.assign(1, None), 4201);
+        assert_eq!(allocator.assign(2, None), 4202);
+        assert_eq!(allocator.assign(1, None), 4201);
+        allocator.release(1);
+        assert_eq!(allocator.assign(3, None), 4203);
+        assert_eq!(allocator.assign(4, None), 4201);
+        assert_eq!(allocator.assign(5, None), CONSPICUITY_CODE);
+    }
+
+    #[test]
+    fn new_settings_reassign_codes() {
+        let mut allocator = SquawkAllocator::new(settings("[squawks]\npool = 4201-4203\n"));
+        assert_eq!(allocator.assign(1, None), 4201);
+        allocator.update_settings(settings("[squawks]\npool = 4201-4203\n"));
+        assert_eq!(allocator.assign(2, None), 4202);
+
+        allocator.update_settings(settings("[squawks]\nmode = orcam\npool = 5301-5302\n[squawk_ranges]\nEG = 4601-4602\n"));
+        assert_eq!(allocator.assign(1, Some("EGLL")), 4601);
+        assert_eq!(allocator.assign(2, Some("LFPG")), 5301);
+    }
+}
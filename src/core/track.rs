@@ -0,0 +1,129 @@
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use fsd_interface::TransponderMode;
+
+use super::{enrichment::AircraftInfo, frequency::Frequency, squawk::CONSPICUITY_CODE, vatsim::RatingLevel};
+
+/// How long a track is kept after its last sample before it is dropped.
+const TRACK_TIMEOUT: Duration = Duration::from_secs(30);
+
+
+
+/// One observation of an aircraft, independent of where it came from.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackSample {
+    pub lat: f64,
+    pub lon: f64,
+    /// Altitude above mean sea level, in feet.
+    pub true_alt: f64,
+    pub pressure_alt: f64,
+    /// Ground speed in knots.
+    pub gs: f64,
+    /// True heading in degrees.
+    pub hdg: f64,
+    /// Vertical speed in feet per minute.
+    pub vs: f64,
+    pub pitch: f64,
+    pub bank: f64,
+    pub on_ground: bool,
+    pub time: Instant,
+}
+
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub callsign: String,
+    pub squawk: u16,
+    pub transponder_mode: TransponderMode,
+    pub rating: RatingLevel,
+    /// What the source knows about the aircraft beyond its position, once it has been fetched.
+    pub info: Option<AircraftInfo>,
+    /// The frequency tuned on COM1, if the source knows it.
+    pub frequency: Option<Frequency>,
+    pub latest: TrackSample,
+    pub previous: Option<TrackSample>,
+}
+
+impl Track {
+    pub fn new(callsign: String, sample: TrackSample) -> Track {
+        Track { callsign, squawk: CONSPICUITY_CODE, transponder_mode: TransponderMode::ModeC, rating: RatingLevel::Student, info: None, frequency: None, latest: sample, previous: None }
+    }
+
+    pub fn update(&mut self, callsign: &str, sample: TrackSample) {
+        if self.callsign != callsign {
+            self.callsign = callsign.to_owned();
+        }
+        self.previous = Some(self.latest);
+        self.latest = sample;
+    }
+
+    /// Rate of change of heading between the last two samples, in degrees per second, right turns positive.
+    pub fn heading_rate(&self) -> f64 {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => return 0.0,
+        };
+        let elapsed = self.latest.time.duration_since(previous.time).as_secs_f64();
+        if elapsed <= 0.0 { return 0.0; }
+        let change = (self.latest.hdg - previous.hdg + 540.0).rem_euclid(360.0) - 180.0;
+        change / elapsed
+    }
+
+    pub fn pitch_rate(&self) -> f64 {
+        self.rate_of(|sample| sample.pitch)
+    }
+
+    pub fn bank_rate(&self) -> f64 {
+        self.rate_of(|sample| sample.bank)
+    }
+
+    fn rate_of(&self, value: impl Fn(&TrackSample) -> f64) -> f64 {
+        let previous = match self.previous {
+            Some(previous) => previous,
+            None => return 0.0,
+        };
+        let elapsed = self.latest.time.duration_since(previous.time).as_secs_f64();
+        if elapsed <= 0.0 { 0.0 } else { (value(&self.latest) - value(&previous)) / elapsed }
+    }
+}
+
+
+/// Tracks keyed by the id their source gives them.
+#[derive(Default)]
+pub struct TrackStore {
+    tracks: HashMap<u32, Track>,
+}
+
+impl TrackStore {
+    pub fn new() -> TrackStore {
+        TrackStore::default()
+    }
+
+    pub fn update(&mut self, id: u32, callsign: &str, sample: TrackSample) -> &mut Track {
+        self.tracks.entry(id)
+            .and_modify(|track| track.update(callsign, sample))
+            .or_insert_with(|| Track::new(callsign.to_owned(), sample))
+    }
+
+    pub fn get(&self, id: u32) -> Option<&Track> {
+        self.tracks.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut Track> {
+        self.tracks.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Option<Track> {
+        self.tracks.remove(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&u32, &Track)> {
+        self.tracks.iter()
+    }
+
+    /// Drops tracks that have not been updated recently and returns them.
+    pub fn prune(&mut self) -> Vec<(u32, Track)> {
+        let now = Instant::now();
+        let stale: Vec<u32> = self.tracks.iter().filter(|(_, track)| now.duration_since(track.latest.time) > TRACK_TIMEOUT).map(|(id, _)| *id).collect();
+        stale.into_iter().filter_map(|id| self.tracks.remove(&id).map(|track| (id, track))).collect()
+    }
+}
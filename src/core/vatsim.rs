@@ -1,36 +1,67 @@
 
-use std::{collections::HashMap, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::Duration};
+use std::{collections::{HashMap, HashSet}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}, time::{Duration, Instant}};
 
 use serde::Deserialize;
 use serde_json::Value;
 
+use super::geo;
+
 const VATSIM_DATA_URL: &str = "https://data.vatsim.net/v3/vatsim-data.json";
 
 #[derive(Clone)]
 pub struct VatsimDataProvider {
     vatsim_aircraft: Arc<Mutex<HashMap<String, VatsimAircraft>>>,
+    vatsim_atis: Arc<Mutex<HashMap<String, AtisDetails>>>,
     last_update_successful: Arc<AtomicBool>,
+    last_update_time: Arc<Mutex<Option<Instant>>>,
 }
 
 impl VatsimDataProvider {
     pub fn new() -> VatsimDataProvider {
         VatsimDataProvider {
             vatsim_aircraft: Arc::new(Mutex::new(HashMap::new())),
+            vatsim_atis: Arc::new(Mutex::new(HashMap::new())),
             last_update_successful: Arc::new(AtomicBool::new(false)),
+            last_update_time: Arc::new(Mutex::new(None)),
         }
     }
     pub fn get_aircraft_details(&self, callsign: &str) -> Option<Details> {
         let lock = self.vatsim_aircraft.lock().unwrap();
         lock.get(callsign).map(|aircraft| aircraft.details.clone())
     }
+    pub fn has_aircraft(&self, callsign: &str) -> bool {
+        self.vatsim_aircraft.lock().unwrap().contains_key(callsign)
+    }
+    pub fn all_aircraft(&self) -> Vec<Details> {
+        self.vatsim_aircraft.lock().unwrap().values().map(|aircraft| aircraft.details.clone()).collect()
+    }
+    /// Pilots within `radius_nm` of a position, as of the last update.
+    pub fn aircraft_near(&self, lat: f64, lon: f64, radius_nm: f64) -> Vec<Details> {
+        let lock = self.vatsim_aircraft.lock().unwrap();
+        lock.values()
+            .filter(|aircraft| geo::distance_nm(lat, lon, aircraft.details.latitude, aircraft.details.longitude) <= radius_nm)
+            .map(|aircraft| aircraft.details.clone())
+            .collect()
+    }
+    pub fn get_atis(&self, callsign: &str) -> Option<AtisDetails> {
+        let lock = self.vatsim_atis.lock().unwrap();
+        lock.get(callsign).cloned()
+    }
 
     pub fn last_update_successful(&self) -> bool {
         self.last_update_successful.load(Ordering::Relaxed)
     }
+    /// When the data was last fetched successfully.
+    pub fn last_update_time(&self) -> Option<Instant> {
+        *self.last_update_time.lock().unwrap()
+    }
 
     pub fn update(&mut self) -> bool {
         let success = self.update_inner();
         self.last_update_successful.store(success, Ordering::Relaxed);
+        if success {
+            *self.last_update_time.lock().unwrap() = Some(Instant::now());
+        }
         success
     }
 
@@ -49,12 +80,14 @@ impl VatsimDataProvider {
             None => return false,
         };
 
+        let mut connected = HashSet::new();
         for value in pilots {
             let new = match serde_json::from_value::<Details>(value.clone()) {
                 Ok(details) => details,
                 Err(_) => continue,
             };
 
+            connected.insert(new.callsign.clone());
             let mut details_map = self.vatsim_aircraft.lock().unwrap();
             if let Some(existing_aircraft) = details_map.get_mut(&new.callsign) {
                 existing_aircraft.update(new);
@@ -64,8 +97,20 @@ impl VatsimDataProvider {
                 details_map.insert(callsign, new_record);
             }
         };
+        // Otherwise pilots who have logged off would still be matched by position
+        self.vatsim_aircraft.lock().unwrap().retain(|callsign, _| connected.contains(callsign));
+
+        let atis_map = json.get("atis").and_then(|atis| atis.as_array()).map(|atis| {
+            atis.iter().filter_map(|value| serde_json::from_value::<AtisDetails>(value.clone()).ok()).map(|atis| (atis.callsign.clone(), atis)).collect::<HashMap<_, _>>()
+        }).unwrap_or_default();
+        *self.vatsim_atis.lock().unwrap() = atis_map;
+
+        true
+    }
 
-        return true;
+    #[cfg(test)]
+    pub fn insert_aircraft(&self, details: Details) {
+        self.vatsim_aircraft.lock().unwrap().insert(details.callsign.clone(), VatsimAircraft::new(details));
     }
 
     pub fn get_details_and_flight_plan_to_send(&mut self, callsign: &str) -> Option<(Details, Option<FlightPlan>)> {
@@ -116,13 +161,6 @@ impl VatsimAircraft {
         self.last_sent_flight_plan_revision_id = flight_plan_to_send.as_ref().map(|fp| fp.revision_id);
         (details, flight_plan_to_send)
     }
-
-    pub fn get_flight_plan(&self) -> Option<FlightPlan> {
-        self.details.flight_plan.clone()
-    }
-    pub fn get_details(&self) -> &Details {
-        &self.details
-    }
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -131,11 +169,117 @@ pub struct Details {
     pub name: String,
     pub callsign: String,
     pub transponder: String,
+    #[serde(default)]
+    pub latitude: f64,
+    #[serde(default)]
+    pub longitude: f64,
     pub altitude: i32,
+    #[serde(default)]
+    pub groundspeed: u32,
     pub heading: u32,
     pub qnh_i_hg: f32,
+    #[serde(default)]
+    pub pilot_rating: u8,
+    #[serde(default)]
+    pub military_rating: u8,
     pub flight_plan: Option<FlightPlan>,
 }
+impl Details {
+    #[cfg(test)]
+    pub fn at(callsign: &str, latitude: f64, longitude: f64, altitude: i32, groundspeed: u32) -> Details {
+        Details {
+            cid: 1000000,
+            name: String::new(),
+            callsign: callsign.to_owned(),
+            transponder: String::from("2000"),
+            latitude,
+            longitude,
+            altitude,
+            groundspeed,
+            heading: 0,
+            qnh_i_hg: 29.92,
+            pilot_rating: 0,
+            military_rating: 0,
+            flight_plan: None,
+        }
+    }
+
+    /// The FSD rating matching the higher of the pilot's civil and military ratings.
+    ///
+    /// Both are bitmasks, with each rating adding a bit to those it builds on. Values the feed
+    /// isn't known to send are taken as no rating at all.
+    pub fn fsd_pilot_rating(&self) -> RatingLevel {
+        let civil = match self.pilot_rating {
+            // PPL
+            1 => RatingLevel::Vfr,
+            // IR, CMEL, ATPL
+            3 | 7 | 15 => RatingLevel::Ifr,
+            // Flight instructor, flight examiner
+            31 | 63 => RatingLevel::Instructor,
+            _ => RatingLevel::Student,
+        };
+        let military = match self.military_rating {
+            // M1, military pilot
+            1 => RatingLevel::Vfr,
+            // M2 instrument, M3 multi-engine, M4 mission ready
+            3 | 7 | 15 => RatingLevel::Ifr,
+            _ => RatingLevel::Student,
+        };
+        civil.max(military)
+    }
+}
+
+/// The FSD pilot ratings, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum RatingLevel {
+    Student = 1,
+    Vfr,
+    Ifr,
+    Instructor,
+    Supervisor,
+}
+impl RatingLevel {
+    pub fn from_name(name: &str) -> Option<RatingLevel> {
+        match name.to_lowercase().as_str() {
+            "student" => Some(RatingLevel::Student),
+            "vfr" => Some(RatingLevel::Vfr),
+            "ifr" => Some(RatingLevel::Ifr),
+            "instructor" => Some(RatingLevel::Instructor),
+            "supervisor" => Some(RatingLevel::Supervisor),
+            _ => None,
+        }
+    }
+    pub fn from_u8(value: u8) -> RatingLevel {
+        match value {
+            2 => RatingLevel::Vfr,
+            3 => RatingLevel::Ifr,
+            4 => RatingLevel::Instructor,
+            5 => RatingLevel::Supervisor,
+            _ => RatingLevel::Student,
+        }
+    }
+}
+impl From<RatingLevel> for fsd_interface::PilotRating {
+    fn from(value: RatingLevel) -> Self {
+        match value {
+            RatingLevel::Student => fsd_interface::PilotRating::Student,
+            RatingLevel::Vfr => fsd_interface::PilotRating::VFR,
+            RatingLevel::Ifr => fsd_interface::PilotRating::IFR,
+            RatingLevel::Instructor => fsd_interface::PilotRating::Instructor,
+            RatingLevel::Supervisor => fsd_interface::PilotRating::Supervisor,
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone)]
+pub struct AtisDetails {
+    pub callsign: String,
+    pub frequency: String,
+    pub atis_code: Option<String>,
+    pub text_atis: Option<Vec<String>>,
+}
 
 
 #[derive(Debug, Deserialize, Clone)]
@@ -163,14 +307,14 @@ pub struct FlightPlan {
 impl FlightPlan {
     pub fn altitude(&self) -> i32 {
         match self.altitude.parse::<i32>() {
-            Ok(alt) => return alt,
+            Ok(alt) => alt,
             Err(_) if self.altitude.starts_with("FL") && self.altitude.len() > 2 => {
                 match self.altitude[2..].parse::<i32>() {
-                    Ok(alt) => return alt * 100,
-                    Err(_) => return 0,
+                    Ok(alt) => alt * 100,
+                    Err(_) => 0,
                 }
             },
-            Err(_) => return 0,
+            Err(_) => 0,
         }
     }
     pub fn departure_time(&self) -> (u8, u8) {
@@ -220,6 +364,7 @@ impl From<FlightPlan> for fsd_interface::FlightPlan {
     }
 }
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Deserialize, Clone)]
 pub enum FlightRules {
     #[serde(rename = "D")]
@@ -230,4 +375,46 @@ pub enum FlightRules {
     SVFR,
     #[serde(rename = "I")]
     IFR,
-}
\ No newline at end of file
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(pilot_rating: u8, military_rating: u8) -> RatingLevel {
+        Details { pilot_rating, military_rating, ..Details::at("BAW12", 0.0, 0.0, 0, 0) }.fsd_pilot_rating()
+    }
+
+    #[test]
+    fn maps_each_pilot_rating() {
+        assert_eq!(rating(0, 0), RatingLevel::Student);
+        assert_eq!(rating(1, 0), RatingLevel::Vfr);
+        assert_eq!(rating(3, 0), RatingLevel::Ifr);
+        assert_eq!(rating(7, 0), RatingLevel::Ifr);
+        assert_eq!(rating(15, 0), RatingLevel::Ifr);
+        assert_eq!(rating(31, 0), RatingLevel::Instructor);
+        assert_eq!(rating(63, 0), RatingLevel::Instructor);
+    }
+
+    #[test]
+    fn maps_each_military_rating() {
+        assert_eq!(rating(0, 1), RatingLevel::Vfr);
+        assert_eq!(rating(0, 3), RatingLevel::Ifr);
+        assert_eq!(rating(0, 7), RatingLevel::Ifr);
+        assert_eq!(rating(0, 15), RatingLevel::Ifr);
+    }
+
+    #[test]
+    fn takes_the_higher_rating() {
+        assert_eq!(rating(1, 3), RatingLevel::Ifr);
+        assert_eq!(rating(31, 15), RatingLevel::Instructor);
+    }
+
+    #[test]
+    fn takes_unknown_values_as_no_rating() {
+        assert_eq!(rating(2, 0), RatingLevel::Student);
+        assert_eq!(rating(0, 31), RatingLevel::Student);
+        assert_eq!(rating(127, 0), RatingLevel::Student);
+    }
+}
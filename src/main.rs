@@ -1,15 +1,22 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+#[cfg(windows)]
 use std::{process, ptr};
+#[cfg(windows)]
 use win32_ui_impl::{util, window, Win32Ui};
+#[cfg(windows)]
 use windows_sys::{w, Win32::{System::LibraryLoader::GetModuleHandleW, UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR}}};
 
 mod core;
 mod ui;
+#[cfg(windows)]
 mod win32_ui_impl;
+#[cfg(not(windows))]
+mod console_ui;
 
 
 
+#[cfg(windows)]
 fn main() {
     
     unsafe {
@@ -24,4 +31,9 @@ fn main() {
         
         Win32Ui::run(hwnd);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(not(windows))]
+fn main() {
+    console_ui::run();
+}
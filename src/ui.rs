@@ -19,12 +19,31 @@ pub enum Message {
     MetarsRetrieved,
     MetarsDisconnected,
 
+    #[cfg_attr(not(windows), allow(unused))]
     MetarNotFound,
+    #[cfg_attr(not(windows), allow(unused))]
     MetarRetrieved(String),
+    #[cfg_attr(not(windows), allow(unused))]
+    MetarsRetrievedMultiple(Vec<String>),
 
     VatsimDataRetrieved,
     VatsimDataDisconnected,
 
+    /// The airport database was read, with this many airports.
+    AirportsLoaded(usize),
+    /// The airport database couldn't be read, for the reason given.
+    AirportsNotLoaded(String),
+
+    /// Traffic is coming from a source other than the sim, named here.
+    SourceConnected(String),
+    SourceDisconnected,
+
+    SquawkSet(u16),
+    TextMessageReceived(String, String),
+    ConfigReloaded,
+    /// A sim aircraft matched to a VATSIM pilot by position: sim id, callsign and confidence in percent.
+    AircraftCorrelated(String, String, u8),
+
     FatalError(String),
 
 }
\ No newline at end of file
@@ -11,21 +11,21 @@ unsafe extern "system" fn about_dialog_proc(hwnd: isize, msg: u32, wparam: usize
         WM_INITDIALOG => {
             let credits_hwnd = GetDlgItem(hwnd, RES_ABOUT_DIALOG_CREDITS_EDITTEXT as i32);
             SendMessageW(credits_hwnd, EM_SETREADONLY, 1, 0);
-            return 0;
+            0
         },
         WM_COMMAND => {
             if wparam == RES_ABOUT_DLG_OK_PUSHBUTTON as usize {
                 EndDialog(hwnd, 1);
                 return 1;
             }
-            return 0;
+            0
         }
         WM_CLOSE => {
             EndDialog(hwnd, 1);
-            return 1;
+            1
         },
 
-        _ => return 0,
+        _ => 0,
     }
 }
 
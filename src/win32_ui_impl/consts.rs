@@ -21,10 +21,12 @@ pub const RES_FETCH_FPS_FROM_VS_CHECKBOX: u32 = 214;
 pub const RES_METAR_STATION_EDITTEXT: u32 = 222;
 pub const RES_FETCH_METAR_PUSHBUTTON: u32 = 223;
 pub const RES_METAR_TEXT: u32 = 224;
+pub const RES_LOG_EDITTEXT: u32 = 231;
 pub const RES_ABOUT_DIALOG_CREDITS_EDITTEXT: u32 = 305;
 pub const RES_ABOUT_DLG_OK_PUSHBUTTON: u32 = 306;
 pub const RES_MENU_MAIN: u32 = 100;
 pub const RES_MENU_MAIN_FILE: u32 = 110;
 pub const RES_MENU_MAIN_FILE_EXIT: u32 = 111;
+pub const RES_MENU_MAIN_FILE_RELOAD: u32 = 112;
 pub const RES_MENU_MAIN_HELP: u32 = 120;
 pub const RES_MENU_MAIN_HELP_ABOUT: u32 = 121;
\ No newline at end of file
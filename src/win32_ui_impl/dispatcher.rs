@@ -30,9 +30,39 @@ impl Ui for MessageDispatcher {
                 lparam = Box::into_raw(Box::new(metar)) as isize;
                 UiMessage::MetarRetrieved
             },
+            Message::MetarsRetrievedMultiple(metars) => {
+                lparam = Box::into_raw(Box::new(metars)) as isize;
+                UiMessage::MetarsRetrievedMultiple
+            },
             Message::MetarNotFound => UiMessage::MetarNotFound,
             Message::VatsimDataRetrieved => UiMessage::VatsimDataRetrieved,
             Message::VatsimDataDisconnected => UiMessage::VatsimDataDisconnected,
+            Message::AirportsLoaded(count) => {
+                lparam = count as isize;
+                UiMessage::AirportsLoaded
+            },
+            Message::AirportsNotLoaded(reason) => {
+                lparam = Box::into_raw(Box::new(reason)) as isize;
+                UiMessage::AirportsNotLoaded
+            },
+            Message::SourceConnected(name) => {
+                lparam = Box::into_raw(Box::new(name)) as isize;
+                UiMessage::SourceConnected
+            },
+            Message::SourceDisconnected => UiMessage::SourceDisconnected,
+            Message::SquawkSet(code) => {
+                lparam = code as isize;
+                UiMessage::SquawkSet
+            },
+            Message::TextMessageReceived(from, message) => {
+                lparam = Box::into_raw(Box::new((from, message))) as isize;
+                UiMessage::TextMessageReceived
+            },
+            Message::ConfigReloaded => UiMessage::ConfigReloaded,
+            Message::AircraftCorrelated(sim_id, callsign, confidence) => {
+                lparam = Box::into_raw(Box::new((sim_id, callsign, confidence))) as isize;
+                UiMessage::AircraftCorrelated
+            },
 
             Message::FatalError(string) => {
                 lparam = Box::into_raw(Box::new(string)) as isize;
@@ -61,10 +91,22 @@ pub enum UiMessage {
 
     MetarNotFound,
     MetarRetrieved,
+    MetarsRetrievedMultiple,
 
     VatsimDataRetrieved,
     VatsimDataDisconnected,
 
+    AirportsLoaded,
+    AirportsNotLoaded,
+
+    SourceConnected,
+    SourceDisconnected,
+
+    SquawkSet,
+    TextMessageReceived,
+    ConfigReloaded,
+    AircraftCorrelated,
+
     FatalError,
 }
 // Euroscope connected
@@ -1,6 +1,6 @@
-use windows_sys::Win32::{Foundation::GetLastError, UI::{Controls::{CheckDlgButton, IsDlgButtonChecked, BST_CHECKED, BST_UNCHECKED, EM_SETLIMITTEXT, EM_SETSEL}, Input::KeyboardAndMouse::{EnableWindow, IsWindowEnabled, SetFocus}, WindowsAndMessaging::{GetDlgItem, GetWindowLongPtrW, SendMessageW, SetWindowLongPtrW, ES_UPPERCASE, GWL_STYLE, WM_GETTEXT, WM_GETTEXTLENGTH, WM_SETTEXT}}};
+use windows_sys::Win32::{UI::{Controls::{CheckDlgButton, IsDlgButtonChecked, BST_CHECKED, BST_UNCHECKED, EM_REPLACESEL, EM_SETLIMITTEXT, EM_SETSEL}, Input::KeyboardAndMouse::{EnableWindow, IsWindowEnabled, SetFocus}, WindowsAndMessaging::{GetDlgItem, GetWindowLongPtrW, SendMessageW, SetWindowLongPtrW, ES_UPPERCASE, GWL_STYLE, WM_GETTEXT, WM_GETTEXTLENGTH, WM_SETTEXT}}};
 
-use super::{consts::{RES_CALLSIGN_EDITTEXT, RES_FETCH_FPS_FROM_VS_CHECKBOX, RES_FETCH_METARS_FROM_VS_CHECKBOX, RES_FETCH_METAR_PUSHBUTTON, RES_METAR_STATION_EDITTEXT, RES_METAR_TEXT, RES_ONLY_SHOW_VS_AC_CHECKBOX, RES_SYNC_WITH_ES_CHECKBOX}, util};
+use super::{consts::{RES_CALLSIGN_EDITTEXT, RES_FETCH_FPS_FROM_VS_CHECKBOX, RES_FETCH_METARS_FROM_VS_CHECKBOX, RES_FETCH_METAR_PUSHBUTTON, RES_LOG_EDITTEXT, RES_METAR_STATION_EDITTEXT, RES_METAR_TEXT, RES_ONLY_SHOW_VS_AC_CHECKBOX, RES_SYNC_WITH_ES_CHECKBOX}, util};
 
 
 
@@ -12,12 +12,13 @@ pub struct MainPage {
     metar_station_input_hwnd: isize,
     metar_text: isize,
     fetch_metar_pushbutton_hwnd: isize,
+    log_hwnd: isize,
     pub only_show_vatsim_aircraft_selected: bool,
 }
 impl MainPage {
     pub unsafe fn new() -> MainPage {
         
-        MainPage { main_hwnd: 0, euroscope_callsign: None, callsign_input_hwnd: 0, metar_station_input_hwnd: 0, metar_text: 0, fetch_metar_pushbutton_hwnd: 0, only_show_vatsim_aircraft_selected: true }
+        MainPage { main_hwnd: 0, euroscope_callsign: None, callsign_input_hwnd: 0, metar_station_input_hwnd: 0, metar_text: 0, fetch_metar_pushbutton_hwnd: 0, log_hwnd: 0, only_show_vatsim_aircraft_selected: true }
         
         
 
@@ -29,6 +30,7 @@ impl MainPage {
         self.metar_station_input_hwnd = GetDlgItem(main_hwnd, RES_METAR_STATION_EDITTEXT as i32);
         self.metar_text = GetDlgItem(main_hwnd, RES_METAR_TEXT as i32);
         self.fetch_metar_pushbutton_hwnd = GetDlgItem(main_hwnd, RES_FETCH_METAR_PUSHBUTTON as i32);
+        self.log_hwnd = GetDlgItem(main_hwnd, RES_LOG_EDITTEXT as i32);
 
 
         // Set max lengths and uppercase only
@@ -36,7 +38,7 @@ impl MainPage {
         let current_style = GetWindowLongPtrW(self.callsign_input_hwnd, GWL_STYLE);
         SetWindowLongPtrW(self.callsign_input_hwnd, GWL_STYLE, current_style | ES_UPPERCASE as isize);
 
-        SendMessageW(self.metar_station_input_hwnd, EM_SETLIMITTEXT, 4, 0);
+        SendMessageW(self.metar_station_input_hwnd, EM_SETLIMITTEXT, 64, 0);
         let current_style = GetWindowLongPtrW(self.metar_station_input_hwnd, GWL_STYLE);
         SetWindowLongPtrW(self.metar_station_input_hwnd, GWL_STYLE, current_style | ES_UPPERCASE as isize);
 
@@ -57,9 +59,6 @@ impl MainPage {
     unsafe fn text_edit_select_all_text(&mut self, text_edit_hwnd: isize) {
         SendMessageW(text_edit_hwnd, EM_SETSEL, 0, -1);
     }
-    pub unsafe fn select_all_callsign_input_text(&mut self) {
-        self.text_edit_select_all_text(self.callsign_input_hwnd);
-    }
     pub unsafe fn select_all_metar_station_input_text(&mut self) {
         self.text_edit_select_all_text(self.metar_station_input_hwnd);
     }
@@ -69,6 +68,15 @@ impl MainPage {
         SendMessageW(self.metar_text, WM_SETTEXT, 0, text.as_ptr() as isize);
     }
 
+    /// Adds a line to the end of the log.
+    pub unsafe fn append_log(&mut self, line: &str) {
+        let length = SendMessageW(self.log_hwnd, WM_GETTEXTLENGTH, 0, 0);
+        SendMessageW(self.log_hwnd, EM_SETSEL, length as usize, length);
+        let separator = if length > 0 { "\r\n" } else { "" };
+        let text = util::wide_null(format!("{}{}", separator, line));
+        SendMessageW(self.log_hwnd, EM_REPLACESEL, 0, text.as_ptr() as isize);
+    }
+
     pub unsafe fn set_metar_button_enabled(&mut self, enabled: bool) {
         let enabled = if enabled { 1 } else { 0 };
         EnableWindow(self.fetch_metar_pushbutton_hwnd, enabled);
@@ -121,30 +129,12 @@ impl MainPage {
     unsafe fn get_checkbox_enabled(&self, checkbox_id: u32) -> bool {
         IsWindowEnabled(GetDlgItem(self.main_hwnd, checkbox_id as i32)) > 0
     }
-    pub unsafe fn set_sync_with_es_checkbox_enabled(&mut self, enabled: bool) {
-        self.set_checkbox_enabled(RES_SYNC_WITH_ES_CHECKBOX, enabled);
-    }
-    pub unsafe fn set_fetch_metars_checkbox_enabled(&mut self, enabled: bool) {
-        self.set_checkbox_enabled(RES_FETCH_METARS_FROM_VS_CHECKBOX, enabled);
-    }
     pub unsafe fn set_only_show_vs_ac_checkbox_enabled(&mut self, enabled: bool) {
         self.set_checkbox_enabled(RES_ONLY_SHOW_VS_AC_CHECKBOX, enabled);
     }
-    pub unsafe fn set_fetch_flight_plans_checkbox_enabled(&mut self, enabled: bool) {
-        self.set_checkbox_enabled(RES_FETCH_FPS_FROM_VS_CHECKBOX, enabled);
-    }
-    pub unsafe fn get_sync_with_es_checkbox_enabled(&self) -> bool {
-        self.get_checkbox_enabled(RES_SYNC_WITH_ES_CHECKBOX)
-    }
-    pub unsafe fn get_fetch_metars_checkbox_enabled(&self) -> bool {
-        self.get_checkbox_enabled(RES_FETCH_METARS_FROM_VS_CHECKBOX)
-    }
     pub unsafe fn get_only_show_vs_ac_checkbox_enabled(&self) -> bool {
         self.get_checkbox_enabled(RES_ONLY_SHOW_VS_AC_CHECKBOX)
     }
-    pub unsafe fn get_sync_wset_fetch_flight_plans_checkbox_enabled(&self) -> bool {
-        self.get_checkbox_enabled(RES_FETCH_FPS_FROM_VS_CHECKBOX)
-    }
 
 
     pub unsafe fn set_callsign_input_enabled(&mut self, enabled: bool) {
@@ -55,7 +55,7 @@ impl StatusBar {
             _ => return false,
         }
 
-        return true;
+        true
     }
 
     pub unsafe fn set_euroscope_connected(&mut self, connected: bool) {
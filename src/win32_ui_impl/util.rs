@@ -1,5 +1,3 @@
-use std::mem;
-
 pub const fn solid_colour(r: u8, g: u8, b: u8) -> u32 {
     let b = (b as u32) << 16;
     let g = (g as u32) << 8;
@@ -15,7 +13,7 @@ pub fn wide_null(s: impl AsRef<str>) -> Vec<u16> {
 
 #[inline]
 pub const unsafe fn make_int_resource(x: u32) -> *const u16 {
-    mem::transmute(x as u64)
+    x as usize as *const u16
 }
 
 #[inline]
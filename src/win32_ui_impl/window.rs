@@ -1,10 +1,10 @@
 use std::{mem, ptr};
 
-use windows_sys::{w, Win32::{Foundation::{HWND, RECT}, Graphics::Gdi::{GetSysColorBrush, COLOR_3DFACE}, System::LibraryLoader::GetModuleHandleW, UI::{Controls::DRAWITEMSTRUCT, Input::KeyboardAndMouse::{GetFocus, IsWindowEnabled}, WindowsAndMessaging::{CreateDialogParamW, CreateWindowExW, DefWindowProcW, DestroyWindow, GetDlgItem, GetWindowLongPtrW, GetWindowRect, LoadCursorW, MessageBoxW, PostQuitMessage, RegisterClassExW, SendMessageW, SetWindowLongPtrW, SetWindowPos, BM_CLICK, CW_USEDEFAULT, DLGWINDOWEXTRA, EN_CHANGE, GWLP_USERDATA, IDC_ARROW, IDOK, MB_ICONERROR, SWP_NOSIZE, SWP_NOZORDER, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_DRAWITEM, WM_NCCREATE, WNDCLASSEXW}}}};
+use windows_sys::{w, Win32::{Foundation::{HWND, RECT}, Graphics::Gdi::{GetSysColorBrush, COLOR_3DFACE}, System::LibraryLoader::GetModuleHandleW, UI::{Controls::DRAWITEMSTRUCT, Input::KeyboardAndMouse::{GetFocus, IsWindowEnabled}, WindowsAndMessaging::{CreateDialogParamW, CreateWindowExW, DefWindowProcW, DestroyWindow, GetDlgItem, GetWindowLongPtrW, GetWindowRect, LoadCursorW, MessageBoxW, PostQuitMessage, RegisterClassExW, SendMessageW, SetWindowLongPtrW, SetWindowPos, BM_CLICK, CW_USEDEFAULT, DLGWINDOWEXTRA, EN_CHANGE, GWLP_USERDATA, IDC_ARROW, MB_ICONERROR, SWP_NOSIZE, SWP_NOZORDER, WM_CLOSE, WM_COMMAND, WM_CREATE, WM_DESTROY, WM_DRAWITEM, WM_NCCREATE, WNDCLASSEXW}}}};
 
 use crate::{core::{App, Preferences}, win32_ui_impl::{consts::{MAIN_DIALOG_CLASS_NAME, RES_MAIN_DIALOG, RES_MENU_MAIN}, util}};
 
-use super::{about_page, consts::{INIT_MESSAGE, RES_FETCH_FPS_FROM_VS_CHECKBOX, RES_FETCH_METARS_FROM_VS_CHECKBOX, RES_FETCH_METAR_PUSHBUTTON, RES_MENU_MAIN_FILE_EXIT, RES_MENU_MAIN_HELP_ABOUT, RES_METAR_STATION_EDITTEXT, RES_CALLSIGN_EDITTEXT, RES_ONLY_SHOW_VS_AC_CHECKBOX, RES_SYNC_WITH_ES_CHECKBOX, UI_MESSAGE}, dispatcher::{MessageDispatcher, UiMessage}, Win32Ui};
+use super::{about_page, consts::{INIT_MESSAGE, RES_FETCH_FPS_FROM_VS_CHECKBOX, RES_FETCH_METARS_FROM_VS_CHECKBOX, RES_FETCH_METAR_PUSHBUTTON, RES_MENU_MAIN_FILE_EXIT, RES_MENU_MAIN_FILE_RELOAD, RES_MENU_MAIN_HELP_ABOUT, RES_METAR_STATION_EDITTEXT, RES_CALLSIGN_EDITTEXT, RES_ONLY_SHOW_VS_AC_CHECKBOX, RES_SYNC_WITH_ES_CHECKBOX, UI_MESSAGE}, dispatcher::{MessageDispatcher, UiMessage}, Win32Ui};
 
 pub unsafe fn setup_window(hinst: isize) -> Result<HWND, String> {
     register_window_class(hinst)?;
@@ -22,29 +22,29 @@ unsafe fn register_window_class(hinst: isize) -> Result<(), String> {
     wnd_class.hInstance = hinst;
     wnd_class.lpszMenuName = util::make_int_resource(RES_MENU_MAIN);
 
-    return if RegisterClassExW(&wnd_class) != 0 {
+    if RegisterClassExW(&wnd_class) != 0 {
         Ok(())
     } else {
         Err("Unable to register window class".into())
-    };
+    }
 }
 
 unsafe fn create_main_window(hinst: isize) -> Result<HWND, String> {
     let hwnd = CreateDialogParamW(hinst, util::make_int_resource(RES_MAIN_DIALOG), 0, None, 0);
-    return if hwnd == 0 {
+    if hwnd == 0 {
         Err("Unable to create main window".into())
     } else {
         Ok(hwnd)
-    };
+    }
 }
 
 unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam: isize) -> isize {
     match msg {
         WM_NCCREATE => {
             let app = App::new(Preferences::new(true, true, true, true), MessageDispatcher::new(hwnd));
-            let ui_boxed = Box::into_raw(Box::new(Win32Ui::new(GetModuleHandleW(ptr::null()), hwnd, app)));
+            let ui_boxed = Box::into_raw(Box::new(Win32Ui::new(GetModuleHandleW(ptr::null()), app)));
             SetWindowLongPtrW(hwnd, GWLP_USERDATA, ui_boxed as isize);
-            return DefWindowProcW(hwnd, msg, wparam, lparam);
+            DefWindowProcW(hwnd, msg, wparam, lparam)
         },
         WM_CREATE => {
             let dummy_hwnd = CreateWindowExW(0, w!("STATIC"), w!("STATIC"), 0, CW_USEDEFAULT, CW_USEDEFAULT, 0, 0, 0, 0, GetModuleHandleW(ptr::null()), ptr::null());
@@ -52,7 +52,7 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
             GetWindowRect(dummy_hwnd, &mut rect);
             DestroyWindow(dummy_hwnd);
             SetWindowPos(hwnd, 0, rect.left, rect.top, 0, 0, SWP_NOSIZE | SWP_NOZORDER);
-            return 0;
+            0
         },
 
         INIT_MESSAGE => {
@@ -99,21 +99,56 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                 },
                 UiMessage::VatsimDataRetrieved => ui.status_bar.set_vatsim_connected(true),
                 UiMessage::VatsimDataDisconnected => ui.status_bar.set_vatsim_connected(false),
+                UiMessage::AirportsLoaded => ui.main_page.append_log(&format!("Loaded {} airports", lparam)),
+                UiMessage::AirportsNotLoaded => {
+                    let reason = *Box::from_raw(lparam as *mut String);
+                    ui.main_page.append_log(&format!("Could not load airports from {}", reason));
+                },
+                UiMessage::SourceConnected => {
+                    let name = *Box::from_raw(lparam as *mut String);
+                    ui.status_bar.set_msfs_connected(true);
+                    ui.main_page.append_log(&format!("Receiving traffic from {}", name));
+                },
+                UiMessage::SourceDisconnected => {
+                    ui.status_bar.set_msfs_connected(false);
+                    ui.main_page.append_log("Traffic source lost");
+                },
 
                 UiMessage::MetarRetrieved => {
                     let metar = *Box::from_raw(lparam as *mut String);
                     ui.main_page.set_metar_text(&metar);
                 },
+                UiMessage::MetarsRetrievedMultiple => {
+                    let metars = *Box::from_raw(lparam as *mut Vec<String>);
+                    // Too many to fit beside the button, so they go in the log where they can be scrolled
+                    ui.main_page.set_metar_text(&format!("{} METARs found, listed in the log", metars.len()));
+                    for metar in &metars {
+                        ui.main_page.append_log(metar);
+                    }
+                },
                 UiMessage::MetarNotFound => {
                     ui.main_page.set_metar_text("METAR not found");
                 }
+                UiMessage::SquawkSet => {
+                    ui.main_page.append_log(&format!("Squawk {:04} set from EuroScope", lparam));
+                }
+                UiMessage::TextMessageReceived => {
+                    let (from, message) = *Box::from_raw(lparam as *mut (String, String));
+                    ui.main_page.append_log(&format!("{}: {}", from, message));
+                }
+                UiMessage::ConfigReloaded => {
+                    ui.main_page.append_log("Settings reloaded");
+                }
+                UiMessage::AircraftCorrelated => {
+                    let (sim_id, callsign, confidence) = *Box::from_raw(lparam as *mut (String, String, u8));
+                    ui.main_page.append_log(&format!("{} matched to {} by position ({}% confidence)", sim_id, callsign, confidence));
+                }
                 UiMessage::FatalError => {
                     let error_string = *Box::from_raw(lparam as *mut String);
                     let wide = util::wide_null(error_string);
                     MessageBoxW(hwnd, wide.as_ptr(), w!("Traffic Viewer"), MB_ICONERROR);
                     SendMessageW(hwnd, WM_CLOSE, 0, 0);
                 },
-                _ => {},
             }
             0
         }
@@ -128,11 +163,11 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
         WM_DRAWITEM => {
             let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
             let dis = &*(lparam as *const DRAWITEMSTRUCT);
-            return if !ui.status_bar.draw(&dis) {
+            if !ui.status_bar.draw(dis) {
                 0
             } else {
                 1
-            };
+            }
         },
 
 
@@ -143,12 +178,17 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                 match lo as u32 {
                     RES_MENU_MAIN_FILE_EXIT => {
                         SendMessageW(hwnd, WM_CLOSE, 0, 0);
-                        return 0;
+                        0
+                    },
+                    RES_MENU_MAIN_FILE_RELOAD => {
+                        let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
+                        ui.app.reload_config();
+                        0
                     },
                     RES_MENU_MAIN_HELP_ABOUT => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
                         about_page::show_about_window(ui.hinst, hwnd);
-                        return 0;
+                        0
                     },
                     RES_SYNC_WITH_ES_CHECKBOX => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
@@ -165,7 +205,7 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                                 ui.main_page.set_callsign_input_text("");
                             }
                         }
-                        return 0;
+                        0
                     },
                     RES_FETCH_METAR_PUSHBUTTON => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
@@ -174,10 +214,10 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                             ui.main_page.set_metar_station_input_focused();
                             return 0;
                         }
-                        ui.app.try_lookup_metar(station);
+                        ui.app.try_search_metars(station);
                         ui.main_page.select_all_metar_station_input_text();
                         ui.main_page.set_metar_station_input_focused();
-                        return 0;
+                        0
                     },
                     RES_ONLY_SHOW_VS_AC_CHECKBOX => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
@@ -187,7 +227,7 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                             ui.main_page.only_show_vatsim_aircraft_selected = checked;
                             ui.app.preferences.set_only_show_vatsim(checked);
                         }
-                        return 0;
+                        0
                     }
                     RES_FETCH_FPS_FROM_VS_CHECKBOX => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
@@ -198,13 +238,13 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                         ui.app.preferences.set_only_show_vatsim(only_show_vs_checked);
                         ui.main_page.set_only_show_vs_ac_checkbox(only_show_vs_checked);
 
-                        return 0;
+                        0
                     }
                     RES_FETCH_METARS_FROM_VS_CHECKBOX => {
                         let ui = &mut *(GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut Win32Ui);
                         let checked = ui.main_page.get_fetch_metars_checkbox();
                         ui.app.preferences.set_fetch_metars(checked);
-                        return 0;
+                        0
                     },
                     
                     1 => {
@@ -213,9 +253,9 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                             SendMessageW(GetDlgItem(hwnd, RES_FETCH_METAR_PUSHBUTTON as i32), BM_CLICK, 0, 0);
                             return 0;
                         }
-                        return DefWindowProcW(hwnd, msg, wparam, lparam);
+                        DefWindowProcW(hwnd, msg, wparam, lparam)
                     }
-                    _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
+                    _ => DefWindowProcW(hwnd, msg, wparam, lparam),
                 }
             } else if hi == EN_CHANGE as u16 {
                 match lo as u32{
@@ -225,15 +265,15 @@ unsafe extern "system" fn wnd_proc(hwnd: isize, msg: u32, wparam: usize, lparam:
                             let text = ui.main_page.get_callsign_input_text();
                             ui.app.preferences.set_own_callsign(text);
                         }
-                        return 0;
+                        0
                     },
-                    _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
+                    _ => DefWindowProcW(hwnd, msg, wparam, lparam),
                 }
             }
-             else { return DefWindowProcW(hwnd, msg, wparam, lparam); }
+             else { DefWindowProcW(hwnd, msg, wparam, lparam) }
         },
 
 
-        _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
     }
 }
\ No newline at end of file